@@ -0,0 +1,245 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::marker::PhantomData;
+
+use futures::{future, Future, Stream, Poll, Async};
+use futures_cpupool::CpuPool;
+use hyper::{Body, Chunk};
+use serde::de::DeserializeOwned;
+use serde_json;
+use digest::{Digest, Output};
+
+use {Request, Error};
+use data::FromBody;
+
+/// Longest record `NdJson` will buffer.
+pub const NDJSON_LINE_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Threads per reactor thread doing blocking file io.
+const FILE_THREADS: usize = 4;
+
+thread_local!(static FILE_POOL: CpuPool = CpuPool::new(FILE_THREADS));
+
+/// Request body as a stream of chunks.
+///
+/// Nothing is read from the connection until the stream is polled,
+/// so a slow consumer slows the client down instead of filling memory.
+pub struct BodyStream {
+    body: Body,
+    limit: Option<u64>,
+    read: u64,
+}
+
+/// Newline-delimited JSON records of a body, see `BodyStream::ndjson`.
+pub struct NdJson<T> {
+    body: BodyStream,
+    buf: Vec<u8>,
+    /// Prefix of `buf` already searched for a newline.
+    scanned: usize,
+    line: usize,
+    eof: bool,
+    _t: PhantomData<T>,
+}
+
+impl BodyStream {
+    pub fn new(body: Body) -> BodyStream {
+        BodyStream {
+            body,
+            limit: None,
+            read: 0,
+        }
+    }
+
+    /// Fails the stream once more than `limit` bytes were received.
+    pub fn limit(mut self, limit: u64) -> BodyStream {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Writes the body to `path`, resolves to the number of bytes written.
+    pub fn save_to<P: AsRef<Path>>(self, path: P) -> Box<Future<Item = u64, Error = Error>> {
+        save_stream(self, path.as_ref(), None)
+    }
+
+    /// Hashes the body chunk by chunk, e.g. `body.hash::<sha2::Sha256>()`.
+    pub fn hash<D: Digest + 'static>(self) -> Box<Future<Item = Output<D>, Error = Error>> {
+        let f = self.fold(D::new(), |mut d, chunk| {
+            d.update(&chunk);
+            Ok::<_, Error>(d)
+        });
+
+        Box::new(f.map(|d| d.finalize()))
+    }
+
+    /// Decodes one JSON value per line, blank lines are skipped.
+    pub fn ndjson<T: DeserializeOwned>(self) -> NdJson<T> {
+        NdJson {
+            body: self,
+            buf: Vec::new(),
+            scanned: 0,
+            line: 0,
+            eof: false,
+            _t: PhantomData,
+        }
+    }
+}
+
+impl FromBody for BodyStream {
+    fn from_body(req: Request) -> Box<Future<Item = Self, Error = Error>> {
+        Box::new(future::ok(BodyStream::new(req.body())))
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = Chunk;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, Error> {
+        let chunk = match try_ready!(self.body.poll()) {
+            Some(chunk) => chunk,
+            None => return Ok(Async::Ready(None)),
+        };
+
+        self.read += chunk.len() as u64;
+        if let Some(limit) = self.limit {
+            if self.read > limit {
+                return Err(Error::PayloadTooLarge(format!("body exceeds {} bytes", limit)));
+            }
+        }
+
+        Ok(Async::Ready(Some(chunk)))
+    }
+}
+
+impl<T: DeserializeOwned> NdJson<T> {
+    fn decode(&mut self, line: &[u8]) -> Result<T, Error> {
+        serde_json::from_slice(line).map_err(|e| {
+            Error::FromDataError(format!("line {}: {}", self.line, e))
+        })
+    }
+}
+
+impl<T: DeserializeOwned> Stream for NdJson<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, Error> {
+        loop {
+            if let Some(i) = self.buf[self.scanned..].iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..self.scanned + i + 1).collect();
+                self.scanned = 0;
+                self.line += 1;
+                if is_blank(&line) {
+                    continue;
+                }
+                return self.decode(&line).map(|v| Async::Ready(Some(v)));
+            }
+
+            if self.eof {
+                if is_blank(&self.buf) {
+                    return Ok(Async::Ready(None));
+                }
+                let line = ::std::mem::replace(&mut self.buf, Vec::new());
+                self.scanned = 0;
+                self.line += 1;
+                return self.decode(&line).map(|v| Async::Ready(Some(v)));
+            }
+
+            if self.buf.len() > NDJSON_LINE_LIMIT {
+                return Err(Error::PayloadTooLarge(
+                    format!("line {} exceeds {} bytes", self.line + 1, NDJSON_LINE_LIMIT),
+                ));
+            }
+
+            self.scanned = self.buf.len();
+            match try_ready!(self.body.poll()) {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => self.eof = true,
+            }
+        }
+    }
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(|b| (*b as char).is_whitespace())
+}
+
+/// Pool for blocking file io, so disk writes and reads don't stall the reactor.
+pub(crate) fn file_pool() -> CpuPool {
+    FILE_POOL.with(|pool| pool.clone())
+}
+
+/// Writes `s` to a new file at `path`, failing once more than `limit` bytes were received.
+///
+/// Writes happen on the file pool, the next chunk is read once the previous one is on disk.
+pub(crate) fn save_stream<S>(s: S, path: &Path, limit: Option<u64>) -> Box<Future<Item = u64, Error = Error>>
+where
+    S: Stream<Item = Chunk, Error = Error> + 'static,
+{
+    let pool = file_pool();
+    let path = path.to_path_buf();
+
+    let f = pool.spawn_fn(move || File::create(path))
+        .map_err(Error::from)
+        .and_then(move |file| {
+            s.fold((file, 0), move |(mut file, size), chunk| {
+                let size = size + chunk.len() as u64;
+                if let Some(limit) = limit {
+                    if size > limit {
+                        let err = Error::PayloadTooLarge(format!("body exceeds {} bytes", limit));
+                        return future::Either::A(future::err(err));
+                    }
+                }
+
+                let write = pool.spawn_fn(move || file.write_all(&chunk).map(|_| (file, size)));
+                future::Either::B(write.map_err(Error::from))
+            })
+        });
+
+    Box::new(f.map(|(_, size)| size))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use futures::Sink;
+
+    #[test]
+    fn ndjson_across_chunks() {
+        let (mut tx, rx) = ::futures::sync::mpsc::channel(4);
+        for chunk in &["{\"a\":1}\n{\"a\"", ":2}\n\n", "{\"a\":3}"] {
+            tx = tx.send(Ok(Chunk::from(*chunk))).wait().unwrap();
+        }
+        drop(tx);
+        let body = Body::from(rx);
+
+        let values: Vec<Value> = BodyStream::new(body).ndjson().collect().wait().unwrap();
+        let a: Vec<u64> = values.iter().map(|v| v["a"].as_u64().unwrap()).collect();
+        assert_eq!(a, vec![1, 2, 3]);
+
+        let err = BodyStream::new(Body::from("{}\nnope\n"))
+            .ndjson::<Value>()
+            .collect()
+            .wait()
+            .unwrap_err();
+        assert!(format!("{:?}", err).contains("line 2"));
+    }
+
+    #[test]
+    fn save_on_file_pool() {
+        let path = ::std::env::temp_dir().join(format!("unrest-body-{}", ::rand::random::<u32>()));
+        let size = BodyStream::new(Body::from("hello")).save_to(&path).wait().unwrap();
+        assert_eq!(size, 5);
+        assert_eq!(::std::fs::read(&path).unwrap(), b"hello");
+
+        let err = save_stream(BodyStream::new(Body::from("hello")), &path, Some(4)).wait();
+        match err {
+            Err(Error::PayloadTooLarge(_)) => {}
+            _ => panic!("expected payload too large"),
+        }
+        ::std::fs::remove_file(&path).unwrap();
+    }
+}
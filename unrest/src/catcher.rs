@@ -0,0 +1,197 @@
+use std::rc::Rc;
+use std::collections::HashMap;
+
+use futures::{future, Future};
+use hyper::{StatusCode, Uri, Headers};
+
+use {Response, Responder, Error};
+use errors::ErrorConfig;
+use request::Method;
+
+/// Renders error responses of one status code instead of the default rendering.
+///
+/// Closures taking `Caught` and returning a `Responder` are catchers too.
+pub trait Catcher {
+    fn catch(&self, Caught) -> Box<Future<Item = Response, Error = Error>>;
+
+    /// Status the catcher is registered for by `Router::register_catcher`,
+    /// set by `#[catcher(..)]`.
+    fn status(&self) -> Option<u16> {
+        None
+    }
+}
+
+/// Error or 4xx/5xx response being caught, with the request it was produced for.
+pub struct Caught {
+    status: StatusCode,
+    method: Method,
+    uri: Uri,
+    headers: Headers,
+    error: Option<Error>,
+    response: Option<Response>,
+    config: ErrorConfig,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Catchers {
+    by_status: HashMap<u16, Rc<Catcher>>,
+}
+
+/// Request line and headers, kept around to pass them to catchers
+/// after the request itself was consumed.
+pub(crate) struct RequestHead {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: Headers,
+}
+
+impl Caught {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    pub fn path(&self) -> &str {
+        self.uri.path()
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// The error, `None` when a handler responded with an error status itself.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// The response that would have been sent without the catcher.
+    pub fn default_response(self) -> Response {
+        match (self.error, self.response) {
+            (Some(e), _) => e.render(&self.config),
+            (None, Some(r)) => r,
+            (None, None) => Response::new().with_status(self.status),
+        }
+    }
+}
+
+impl<F, R> Catcher for F
+where
+    F: Fn(Caught) -> R,
+    R: Responder,
+{
+    fn catch(&self, caught: Caught) -> Box<Future<Item = Response, Error = Error>> {
+        Box::new(future::ok(self(caught).respond()))
+    }
+}
+
+impl Catchers {
+    pub fn add(&mut self, status: u16, catcher: Rc<Catcher>) {
+        self.by_status.insert(status, catcher);
+    }
+
+    /// Catchers of `other` win over ours.
+    pub fn merge(&mut self, other: &Catchers) {
+        for (status, c) in &other.by_status {
+            self.by_status.insert(*status, c.clone());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_status.is_empty()
+    }
+
+    /// Runs the catcher for the status of `res`, if there is one,
+    /// errors without one are rendered with `config`.
+    pub fn run(
+        &self,
+        head: &RequestHead,
+        res: Result<Response, Error>,
+        config: &ErrorConfig,
+    ) -> Box<Future<Item = Response, Error = Error>> {
+        let (status, error, response) = match res {
+            Ok(r) => {
+                let status = r.status();
+                if !status.is_client_error() && !status.is_server_error() {
+                    return Box::new(future::ok(r));
+                }
+                (status, None, Some(r))
+            }
+            Err(e) => (e.status_code(), Some(e), None),
+        };
+
+        let catcher = match self.by_status.get(&status.as_u16()) {
+            Some(c) => c.clone(),
+            None => {
+                return match error {
+                    Some(e) => Box::new(future::ok(e.render(config))),
+                    None => Box::new(future::ok(response.unwrap())),
+                }
+            }
+        };
+
+        let caught = Caught {
+            status,
+            method: head.method.clone(),
+            uri: head.uri.clone(),
+            headers: head.headers.clone(),
+            error,
+            response,
+            config: *config,
+        };
+
+        Box::new(catcher.catch(caught).map(move |r| if r.status() == StatusCode::Ok {
+            r.with_status(status)
+        } else {
+            r
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catch_by_status() {
+        let mut catchers = Catchers::default();
+        catchers.add(
+            404,
+            Rc::new(|c: Caught| if c.path().starts_with("/api") {
+                c.default_response()
+            } else {
+                Response::new().with_body(format!("no page at {}", c.path()))
+            }),
+        );
+
+        let head = |path: &str| {
+            RequestHead {
+                method: Method::Get,
+                uri: path.parse().unwrap(),
+                headers: Headers::new(),
+            }
+        };
+
+        let config = ErrorConfig::default();
+        let r = catchers.run(&head("/users/1"), Err(Error::NotFound), &config).wait().unwrap();
+        assert_eq!(r.status(), StatusCode::NotFound);
+        assert_eq!(r.headers().get_raw("Content-Type"), None);
+
+        let r = catchers.run(&head("/api/users/1"), Err(Error::NotFound), &config).wait().unwrap();
+        assert_eq!(r.status(), StatusCode::NotFound);
+        assert!(r.headers().get_raw("Content-Type").is_some());
+
+        let r = catchers
+            .run(&head("/x"), Err(Error::Forbidden("no".to_string())), &config)
+            .wait()
+            .unwrap();
+        assert_eq!(r.status(), StatusCode::Forbidden);
+    }
+}
@@ -0,0 +1,219 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use cookie::CookieJar;
+use futures::{future, Future};
+use hyper::header::SetCookie;
+
+use {Request, Response, Params, Error, Container, State};
+use request::ResponseHook;
+use from_request::FromRequest;
+
+pub use cookie::{Cookie, Key, SameSite};
+
+/// Keys used to sign and encrypt private cookies.
+///
+/// Managed as server state. New cookies are always sealed with the current key,
+/// previous keys are only used to read cookies issued before a rotation.
+pub struct CookieKeys {
+    current: Key,
+    previous: Vec<Key>,
+}
+
+impl CookieKeys {
+    pub fn new(current: Key) -> CookieKeys {
+        CookieKeys {
+            current,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Random keys, cookies sealed with them won't survive a restart.
+    pub fn generate() -> CookieKeys {
+        CookieKeys::new(Key::generate())
+    }
+
+    /// Makes `key` the current key, keeping the old one around for reading.
+    pub fn rotate(mut self, key: Key) -> CookieKeys {
+        let old = ::std::mem::replace(&mut self.current, key);
+        self.previous.insert(0, old);
+        self
+    }
+
+    /// Adds a key that is still accepted when reading but never used for writing.
+    pub fn with_previous(mut self, key: Key) -> CookieKeys {
+        self.previous.push(key);
+        self
+    }
+}
+
+/// Cookie jar of the current request.
+///
+/// All changes are written to the response as `Set-Cookie` headers.
+#[derive(Clone)]
+pub struct Cookies {
+    jar: Rc<RefCell<CookieJar>>,
+    keys: State<CookieKeys>,
+}
+
+struct WriteCookies(Cookies);
+
+impl Cookies {
+    fn new(jar: CookieJar, keys: State<CookieKeys>) -> Cookies {
+        Cookies {
+            jar: Rc::new(RefCell::new(jar)),
+            keys,
+        }
+    }
+
+    /// Returns the jar of `req`, all calls within one request share it.
+    pub fn for_request(req: &Request, state: &Container) -> Result<Cookies, Error> {
+        if let Some(cookies) = req.local::<Cookies>() {
+            return Ok(cookies);
+        }
+
+        let keys = state.get::<CookieKeys>().ok_or_else(|| {
+            Error::StateNotFound("CookieKeys".to_string())
+        })?;
+
+        let mut jar = CookieJar::new();
+        if let Some(raw) = req.headers().get_raw("Cookie") {
+            for line in raw.iter() {
+                let line = match ::std::str::from_utf8(line) {
+                    Ok(line) => line,
+                    Err(_) => continue,
+                };
+                for s in line.split(';') {
+                    if let Ok(c) = Cookie::parse_encoded(s.trim().to_string()) {
+                        jar.add_original(c);
+                    }
+                }
+            }
+        }
+
+        let cookies = Cookies::new(jar, keys);
+        req.set_local(cookies.clone());
+        req.on_response(WriteCookies(cookies.clone()));
+
+        Ok(cookies)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        self.jar.borrow().get(name).cloned()
+    }
+
+    pub fn add(&self, cookie: Cookie<'static>) {
+        self.jar.borrow_mut().add(cookie)
+    }
+
+    pub fn remove(&self, cookie: Cookie<'static>) {
+        self.jar.borrow_mut().remove(cookie)
+    }
+
+    /// Returns a decrypted cookie, `None` if it's missing or was tampered with.
+    pub fn get_private(&self, name: &str) -> Option<Cookie<'static>> {
+        let mut jar = self.jar.borrow_mut();
+        if let Some(c) = jar.private(&self.keys.current).get(name) {
+            return Some(c);
+        }
+
+        for key in &self.keys.previous {
+            if let Some(c) = jar.private(key).get(name) {
+                return Some(c);
+            }
+        }
+
+        None
+    }
+
+    /// Adds a cookie encrypted and authenticated with AEAD.
+    pub fn add_private(&self, cookie: Cookie<'static>) {
+        self.jar.borrow_mut().private(&self.keys.current).add(cookie)
+    }
+
+    pub fn remove_private(&self, cookie: Cookie<'static>) {
+        self.jar.borrow_mut().private(&self.keys.current).remove(cookie)
+    }
+
+    /// Returns a cookie with a verified signature, `None` if it's missing or was tampered with.
+    pub fn get_signed(&self, name: &str) -> Option<Cookie<'static>> {
+        let mut jar = self.jar.borrow_mut();
+        if let Some(c) = jar.signed(&self.keys.current).get(name) {
+            return Some(c);
+        }
+
+        for key in &self.keys.previous {
+            if let Some(c) = jar.signed(key).get(name) {
+                return Some(c);
+            }
+        }
+
+        None
+    }
+
+    /// Adds a cookie signed with HMAC, the value stays readable by the client.
+    pub fn add_signed(&self, cookie: Cookie<'static>) {
+        self.jar.borrow_mut().signed(&self.keys.current).add(cookie)
+    }
+
+    pub fn remove_signed(&self, cookie: Cookie<'static>) {
+        self.jar.borrow_mut().signed(&self.keys.current).remove(cookie)
+    }
+
+    fn write_delta(&self, resp: &mut Response) {
+        let jar = self.jar.borrow();
+        let mut delta: Vec<String> = jar.delta().map(|c| c.encoded().to_string()).collect();
+        if delta.is_empty() {
+            return;
+        }
+
+        if let Some(set_cookie) = resp.headers_mut().get_mut::<SetCookie>() {
+            set_cookie.0.append(&mut delta);
+            return;
+        }
+        resp.headers_mut().set(SetCookie(delta));
+    }
+}
+
+impl FromRequest for Cookies {
+    fn from_request(req: &Request, _: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        Box::new(future::result(Cookies::for_request(req, state)))
+    }
+}
+
+impl ResponseHook for WriteCookies {
+    fn on_response(&self, mut resp: Response) -> Box<Future<Item = Response, Error = Error>> {
+        self.0.write_delta(&mut resp);
+        Box::new(future::ok(resp))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookies(keys: CookieKeys, jar: CookieJar) -> Cookies {
+        let mut c = Container::new();
+        c.set(keys);
+        Cookies::new(jar, c.get::<CookieKeys>().unwrap())
+    }
+
+    #[test]
+    fn private_survives_rotation() {
+        let old = Key::generate();
+        let c = cookies(CookieKeys::new(old.clone()), CookieJar::new());
+        c.add_private(Cookie::new("user", "42"));
+        c.add_signed(Cookie::new("theme", "dark"));
+
+        let mut jar = CookieJar::new();
+        for cookie in c.jar.borrow().iter() {
+            jar.add_original(cookie.clone());
+        }
+
+        let c = cookies(CookieKeys::new(old).rotate(Key::generate()), jar);
+        assert_eq!(c.get_private("user").unwrap().value(), "42");
+        assert_eq!(c.get_signed("theme").unwrap().value(), "dark");
+        assert!(c.get_private("theme").is_none());
+    }
+}
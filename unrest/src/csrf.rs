@@ -0,0 +1,353 @@
+use std::fmt;
+use std::ops::Deref;
+
+use futures::{future, Future, Stream};
+use hyper::{Method, Body};
+use hyper::header::ContentType;
+use hyper::mime;
+use rand::{self, Rng};
+use url::{Url, form_urlencoded};
+
+use {Request, Params, Error, Container, Cookies, Cookie, SameSite, BodyStream};
+use middleware::Middleware;
+use from_request::FromRequest;
+
+const COOKIE_NAME: &'static str = "unrest_csrf";
+const HEADER_NAME: &'static str = "X-CSRF-Token";
+const FORM_FIELD: &'static str = "_csrf";
+
+/// Largest form body read to find the `_csrf` field, see `Csrf::form_limit`.
+pub const CSRF_FORM_LIMIT: u64 = 1024 * 1024;
+
+/// CSRF protection with double-submit tokens.
+///
+/// The token lives in a private cookie, unsafe requests must echo it back either in
+/// the `X-CSRF-Token` header or in the `_csrf` field of an urlencoded form.
+/// `Origin` (or `Referer` when it's absent) must match the allowed origins,
+/// by default the request's own `Host`.
+pub struct Csrf {
+    allowed_origins: Vec<String>,
+    exempt: Vec<String>,
+    form_limit: u64,
+}
+
+/// CSRF token of the current request, embed it into forms as `_csrf`.
+#[derive(Clone, Debug)]
+pub struct CsrfToken(String);
+
+impl Csrf {
+    pub fn new() -> Csrf {
+        Csrf {
+            allowed_origins: Vec::new(),
+            exempt: Vec::new(),
+            form_limit: CSRF_FORM_LIMIT,
+        }
+    }
+
+    /// Accepts requests from `origin`, e.g. `https://example.com`.
+    pub fn allow_origin(mut self, origin: &str) -> Csrf {
+        self.allowed_origins.push(origin.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Skips the check for all paths starting with `prefix`.
+    pub fn exempt(mut self, prefix: &str) -> Csrf {
+        self.exempt.push(prefix.to_string());
+        self
+    }
+
+    /// Forms larger than `limit` bytes are answered with 413.
+    pub fn form_limit(mut self, limit: u64) -> Csrf {
+        self.form_limit = limit;
+        self
+    }
+
+    fn check_origin(&self, req: &Request) -> Result<(), Error> {
+        let origin = match request_origin(req) {
+            Some(origin) => origin,
+            None => return Ok(()),
+        };
+
+        let allowed = if self.allowed_origins.is_empty() {
+            let host = req.headers().get_raw("Host").and_then(|raw| raw.one()).and_then(
+                |h| ::std::str::from_utf8(h).ok(),
+            );
+            match (Url::parse(&origin), host) {
+                (Ok(url), Some(host)) => authority(&url).map_or(false, |a| a == host),
+                _ => false,
+            }
+        } else {
+            self.allowed_origins.iter().any(|o| *o == origin)
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::Forbidden(format!("csrf: origin '{}' is not allowed", origin)))
+        }
+    }
+}
+
+impl Middleware for Csrf {
+    fn handle(&self, mut req: Request, state: &Container) -> Box<Future<Item = Request, Error = Error>> {
+        if is_safe(req.method()) || self.exempt.iter().any(|p| req.path().starts_with(p)) {
+            return Box::new(future::ok(req));
+        }
+
+        if let Err(e) = self.check_origin(&req) {
+            return Box::new(future::err(e));
+        }
+
+        let expected = match Cookies::for_request(&req, state) {
+            Ok(cookies) => cookies.get_private(COOKIE_NAME),
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let expected = match expected {
+            Some(c) => c.value().to_string(),
+            None => return Box::new(future::err(Error::Forbidden("csrf: missing token cookie".to_string()))),
+        };
+
+        let header = req.headers()
+            .get_raw(HEADER_NAME)
+            .and_then(|raw| raw.one())
+            .map(|h| h.to_vec());
+        if let Some(header) = header {
+            return Box::new(future::result(verify(&expected, &header).map(|_| req)));
+        }
+
+        let is_form = req.headers().get::<ContentType>().map_or(false, |ct| {
+            ct.type_() == mime::APPLICATION && ct.subtype() == mime::WWW_FORM_URLENCODED
+        });
+        if !is_form {
+            return Box::new(future::err(Error::Forbidden("csrf: missing token".to_string())));
+        }
+
+        let body = BodyStream::new(req.take_body()).limit(self.form_limit);
+        let f = body.concat2().and_then(
+            move |chunk| {
+                let submitted = form_urlencoded::parse(&chunk)
+                    .find(|&(ref k, _)| k == FORM_FIELD)
+                    .map(|(_, v)| v.into_owned())
+                    .unwrap_or_default();
+                verify(&expected, submitted.as_bytes())?;
+
+                req.set_body(Body::from(chunk.to_vec()));
+                Ok(req)
+            },
+        );
+
+        Box::new(f)
+    }
+}
+
+impl CsrfToken {
+    /// Returns the token, issuing a new one if the client has none yet.
+    pub fn for_request(req: &Request, state: &Container) -> Result<CsrfToken, Error> {
+        if let Some(token) = req.local::<CsrfToken>() {
+            return Ok(token);
+        }
+
+        let cookies = Cookies::for_request(req, state)?;
+        let token = match cookies.get_private(COOKIE_NAME) {
+            Some(c) => c.value().to_string(),
+            None => {
+                let token: String = rand::thread_rng().gen_ascii_chars().take(32).collect();
+                cookies.add_private(
+                    Cookie::build(COOKIE_NAME, token.clone())
+                        .path("/")
+                        .http_only(true)
+                        .same_site(SameSite::Strict)
+                        .finish(),
+                );
+                token
+            }
+        };
+
+        let token = CsrfToken(token);
+        req.set_local(token.clone());
+        Ok(token)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromRequest for CsrfToken {
+    fn from_request(req: &Request, _: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        Box::new(future::result(CsrfToken::for_request(req, state)))
+    }
+}
+
+impl Deref for CsrfToken {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn is_safe(m: &Method) -> bool {
+    match *m {
+        Method::Get | Method::Head | Method::Options | Method::Trace => true,
+        _ => false,
+    }
+}
+
+fn request_origin(req: &Request) -> Option<String> {
+    let raw = |name| {
+        req.headers()
+            .get_raw(name)
+            .and_then(|raw| raw.one())
+            .and_then(|h| ::std::str::from_utf8(h).ok())
+    };
+
+    match raw("Origin") {
+        Some(origin) if origin != "null" => Some(origin.trim_end_matches('/').to_string()),
+        Some(origin) => Some(origin.to_string()),
+        None => {
+            raw("Referer").map(|referer| match Url::parse(referer) {
+                Ok(url) => url.origin().ascii_serialization(),
+                Err(_) => "null".to_string(),
+            })
+        }
+    }
+}
+
+fn authority(url: &Url) -> Option<String> {
+    url.host_str().map(|host| match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+fn verify(expected: &str, submitted: &[u8]) -> Result<(), Error> {
+    let expected = expected.as_bytes();
+    if expected.len() != submitted.len() {
+        return Err(Error::Forbidden("csrf: invalid token".to_string()));
+    }
+
+    let diff = expected.iter().zip(submitted).fold(0, |acc, (a, b)| acc | (a ^ b));
+    if diff == 0 {
+        Ok(())
+    } else {
+        Err(Error::Forbidden("csrf: invalid token".to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Request as HyperRequest;
+    use hyper::header::SetCookie;
+    use CookieKeys;
+
+    fn request(headers: &[(&'static str, &'static str)]) -> Request {
+        let mut r = HyperRequest::new(Method::Post, "/form".parse().unwrap());
+        for &(name, value) in headers {
+            r.headers_mut().set_raw(name, value);
+        }
+        Request::new(r)
+    }
+
+    #[test]
+    fn origin_check() {
+        let csrf = Csrf::new();
+        assert!(csrf.check_origin(&request(&[("Host", "example.com")])).is_ok());
+        assert!(
+            csrf.check_origin(&request(&[("Host", "example.com"), ("Origin", "http://example.com")]))
+                .is_ok()
+        );
+        assert!(
+            csrf.check_origin(&request(&[("Host", "example.com"), ("Origin", "http://evil.com")]))
+                .is_err()
+        );
+        assert!(
+            csrf.check_origin(&request(
+                &[("Host", "example.com:8080"), ("Referer", "http://example.com:8080/form")],
+            )).is_ok()
+        );
+
+        let csrf = Csrf::new().allow_origin("https://app.example.com");
+        assert!(
+            csrf.check_origin(&request(
+                &[("Host", "api.example.com"), ("Origin", "https://app.example.com")],
+            )).is_ok()
+        );
+    }
+
+    /// Issues a token, returns it with the cookie carrying it.
+    fn token(state: &Container) -> (String, String) {
+        let req = Request::new(HyperRequest::new(Method::Get, "/form".parse().unwrap()));
+        let token = CsrfToken::for_request(&req, state).unwrap().as_str().to_string();
+        let resp = req.extensions().run_hooks(::Response::new()).wait().unwrap();
+        let cookie = resp.headers().get::<SetCookie>().unwrap().0[0].clone();
+        let cookie = cookie.split(';').next().unwrap().to_string();
+        (token, cookie)
+    }
+
+    fn post(path: &str, headers: Vec<(&'static str, String)>, body: &str) -> Request {
+        let mut r = HyperRequest::new(Method::Post, path.parse().unwrap());
+        r.headers_mut().set_raw("Host", "example.com");
+        for (name, value) in headers {
+            r.headers_mut().set_raw(name, value);
+        }
+        r.set_body(body.to_string());
+        Request::new(r)
+    }
+
+    fn forbidden(res: Result<Request, Error>) -> String {
+        match res {
+            Err(Error::Forbidden(reason)) => reason,
+            Err(e) => panic!("expected 403, got {:?}", e),
+            Ok(_) => panic!("expected 403, request passed"),
+        }
+    }
+
+    #[test]
+    fn token_checks() {
+        let mut state = Container::new();
+        state.set(CookieKeys::generate());
+        let (token, cookie) = token(&state);
+        let csrf = Csrf::new().exempt("/hooks");
+        let form = || ("Content-Type", "application/x-www-form-urlencoded".to_string());
+
+        let req = post("/form", vec![("Cookie", cookie.clone()), (HEADER_NAME, token.clone())], "");
+        assert!(csrf.handle(req, &state).wait().is_ok());
+
+        let req = post("/form", vec![("Cookie", cookie.clone()), (HEADER_NAME, "x".repeat(32))], "");
+        assert_eq!(forbidden(csrf.handle(req, &state).wait()), "csrf: invalid token");
+
+        let body = format!("name=a&_csrf={}", token);
+        let req = post("/form", vec![("Cookie", cookie.clone()), form()], &body);
+        let req = csrf.handle(req, &state).wait().unwrap();
+        assert_eq!(&req.body().concat2().wait().unwrap()[..], body.as_bytes());
+
+        let req = post("/form", vec![("Cookie", cookie.clone()), form()], "name=a&_csrf=nope");
+        assert_eq!(forbidden(csrf.handle(req, &state).wait()), "csrf: invalid token");
+
+        let req = post("/form", vec![("Cookie", cookie.clone())], "name=a");
+        assert_eq!(forbidden(csrf.handle(req, &state).wait()), "csrf: missing token");
+
+        let req = post("/form", vec![(HEADER_NAME, token.clone())], "");
+        assert_eq!(forbidden(csrf.handle(req, &state).wait()), "csrf: missing token cookie");
+
+        let req = post("/hooks/deploy", vec![], "");
+        assert!(csrf.handle(req, &state).wait().is_ok());
+
+        let csrf = Csrf::new().form_limit(16);
+        let body = format!("name={}&_csrf={}", "a".repeat(32), token);
+        let req = post("/form", vec![("Cookie", cookie), form()], &body);
+        match csrf.handle(req, &state).wait() {
+            Err(Error::PayloadTooLarge(_)) => {}
+            _ => panic!("expected 413 for a form over the limit"),
+        }
+    }
+}
@@ -1,15 +1,274 @@
 use futures::{Future, Stream};
+use hyper::header::{Headers, ContentType, Accept, q};
+use hyper::mime::{self, Mime};
+use serde::de::DeserializeOwned;
+use serde_json;
+
 use {Request, Error};
+use urlencoded;
 
+/// Data argument parsed from the buffered body.
+///
+/// `content_type` is the request's `Content-Type`, if it has one.
 pub trait FromData: Sized {
-    fn from_data(&[u8]) -> Result<Self, Error>;
+    fn from_data(data: &[u8], content_type: Option<&Mime>) -> Result<Self, Error>;
+}
+
+/// Data argument built from the whole request.
+///
+/// Every `FromData` type gets it by buffering the body, implement it directly
+/// for types that consume the body as a stream.
+pub trait FromBody: Sized {
+    fn from_body(Request) -> Box<Future<Item = Self, Error = Error>>;
+}
+
+/// Body formats known to `#[handler(.., format = "json")]` and `Negotiated`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Form,
+    Multipart,
+    MsgPack,
+    Cbor,
+    Yaml,
+    Toml,
+    Protobuf,
+    Text,
+}
+
+/// Body deserialized according to its `Content-Type`, JSON when there is none.
+///
+/// Formats other than JSON and forms are only accepted with their cargo feature enabled.
+#[derive(Debug)]
+pub struct Negotiated<T> {
+    pub value: T,
+    pub format: Format,
+}
+
+impl<T: FromData + 'static> FromBody for T {
+    fn from_body(req: Request) -> Box<Future<Item = Self, Error = Error>> {
+        let content_type = req.headers().get::<ContentType>().map(|ct| ct.0.clone());
+
+        Box::new(req.body().concat2().map_err(|e| e.into()).and_then(
+            move |chunk| {
+                FromData::from_data(&*chunk, content_type.as_ref())
+            },
+        ))
+    }
+}
+
+pub fn from_data_req<T: FromBody>(req: Request) -> Box<Future<Item = T, Error = Error>> {
+    T::from_body(req)
+}
+
+impl Format {
+    /// `"json"` -> `Format::Json`, the names accepted by `format = ".."`.
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "form" => Some(Format::Form),
+            "multipart" => Some(Format::Multipart),
+            "msgpack" => Some(Format::MsgPack),
+            "cbor" => Some(Format::Cbor),
+            "yaml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "protobuf" => Some(Format::Protobuf),
+            "text" => Some(Format::Text),
+            _ => None,
+        }
+    }
+
+    /// Format of a media type, `application/*+json` counts as JSON.
+    pub fn of(m: &Mime) -> Option<Format> {
+        let format = match (m.type_().as_str(), m.subtype().as_str()) {
+            ("application", "json") => Format::Json,
+            ("application", "x-www-form-urlencoded") => Format::Form,
+            ("multipart", "form-data") => Format::Multipart,
+            ("application", "msgpack") |
+            ("application", "x-msgpack") => Format::MsgPack,
+            ("application", "cbor") => Format::Cbor,
+            ("application", "yaml") |
+            ("application", "x-yaml") |
+            ("text", "yaml") => Format::Yaml,
+            ("application", "toml") => Format::Toml,
+            ("application", "protobuf") |
+            ("application", "x-protobuf") => Format::Protobuf,
+            ("text", "plain") => Format::Text,
+            ("application", _) if m.suffix() == Some(mime::JSON) => Format::Json,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Media type sent in `Content-Type` for this format.
+    pub fn mime(&self) -> Mime {
+        let s = match *self {
+            Format::Json => "application/json",
+            Format::Form => "application/x-www-form-urlencoded",
+            Format::Multipart => "multipart/form-data",
+            Format::MsgPack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+            Format::Yaml => "application/yaml",
+            Format::Toml => "application/toml",
+            Format::Protobuf => "application/x-protobuf",
+            Format::Text => "text/plain; charset=utf-8",
+        };
+        s.parse().unwrap()
+    }
+
+    fn is_text(&self) -> bool {
+        match *self {
+            Format::Json | Format::Form | Format::Yaml | Format::Toml | Format::Text => true,
+            _ => false,
+        }
+    }
+}
+
+/// Picks the format from `formats` the client prefers, ties go to the earlier one.
+/// Wildcards like `*/*` match the first format.
+pub fn preferred_format(accept: &Accept, formats: &[Format]) -> Option<Format> {
+    let mut best: Option<(Format, usize)> = None;
+    let mut best_quality = q(0.0);
+
+    for item in accept.iter().filter(|item| item.quality > q(0.0)) {
+        let m = &item.item;
+        let candidates = formats.iter().enumerate().filter(|&(_, f)| {
+            if m.type_() == mime::STAR {
+                true
+            } else if m.subtype() == mime::STAR {
+                f.mime().type_() == m.type_()
+            } else {
+                Format::of(m) == Some(*f)
+            }
+        });
+
+        for (i, f) in candidates {
+            let better = match best {
+                None => true,
+                Some((_, best_i)) => {
+                    item.quality > best_quality || (item.quality == best_quality && i < best_i)
+                }
+            };
+            if better {
+                best = Some((*f, i));
+                best_quality = item.quality;
+            }
+        }
+    }
+
+    best.map(|(f, _)| f)
+}
+
+/// Rejects a body declared with another format or a non UTF-8 charset,
+/// a missing `Content-Type` is let through.
+pub fn expect_format(content_type: Option<&Mime>, format: Format) -> Result<(), Error> {
+    let m = match content_type {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+
+    if Format::of(m) != Some(format) {
+        return Err(Error::UnsupportedMediaType(
+            format!("expected {}, got {}", format.mime(), m),
+        ));
+    }
+
+    match m.get_param(mime::CHARSET) {
+        Some(charset) if format.is_text() && charset != mime::UTF_8 => {
+            Err(Error::UnsupportedMediaType(format!("unsupported charset {}", charset)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check behind `#[handler(.., format = "json")]`, here the `Content-Type` is required.
+pub fn require_format(headers: &Headers, name: &str) -> Result<(), Error> {
+    let format = Format::from_name(name).ok_or_else(|| {
+        Error::OtherServersFault(format!("unknown data format '{}'", name))
+    })?;
+
+    match headers.get::<ContentType>() {
+        Some(ct) => expect_format(Some(&ct.0), format),
+        None => Err(Error::UnsupportedMediaType(
+            format!("expected {}, got no content type", format.mime()),
+        )),
+    }
+}
+
+impl<T: DeserializeOwned> FromData for Negotiated<T> {
+    fn from_data(buf: &[u8], content_type: Option<&Mime>) -> Result<Self, Error> {
+        let format = match content_type {
+            Some(m) => {
+                Format::of(m).ok_or_else(|| {
+                    Error::UnsupportedMediaType(format!("unsupported content type {}", m))
+                })?
+            }
+            None => Format::Json,
+        };
+        expect_format(content_type, format)?;
+
+        let value = match format {
+            Format::Json => serde_json::from_slice(buf).map_err(|e| e.to_string()),
+            Format::Form => urlencoded::from_bytes(buf).map_err(|e| e.to_string()),
+            #[cfg(feature = "msgpack")]
+            Format::MsgPack => ::rmp_serde::from_slice(buf).map_err(|e| e.to_string()),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => ::serde_cbor::from_slice(buf).map_err(|e| e.to_string()),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => ::serde_yaml::from_slice(buf).map_err(|e| e.to_string()),
+            #[cfg(feature = "toml")]
+            Format::Toml => ::toml::from_slice(buf).map_err(|e| e.to_string()),
+            _ => {
+                return Err(Error::UnsupportedMediaType(
+                    format!("unsupported content type {}", format.mime()),
+                ))
+            }
+        };
+
+        value.map(|value| Negotiated { value, format }).map_err(
+            Error::FromDataError,
+        )
+    }
 }
 
-pub fn from_data_req<T: FromData>(req: Request) -> impl Future<Item = T, Error = Error> {
-    req.body().concat2().map_err(|e| e.into()).and_then(
-        |chunk| {
-            FromData::from_data(&*chunk)
-        },
-    )
 
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn content_type_dispatch() {
+        let json: Mime = "application/vnd.api+json".parse().unwrap();
+        let form: Mime = "application/x-www-form-urlencoded; charset=utf-8".parse().unwrap();
+        let latin: Mime = "application/json; charset=iso-8859-1".parse().unwrap();
+        let text = mime::TEXT_PLAIN;
+
+        let n: Negotiated<Value> = Negotiated::from_data(b"{\"a\":1}", Some(&json)).unwrap();
+        assert_eq!((n.format, n.value["a"].as_u64()), (Format::Json, Some(1)));
+
+        let n: Negotiated<Value> = Negotiated::from_data(b"a=1", Some(&form)).unwrap();
+        assert_eq!((n.format, n.value["a"].as_str()), (Format::Form, Some("1")));
+
+        assert!(Negotiated::<Value>::from_data(b"{}", None).is_ok());
+        assert!(Negotiated::<Value>::from_data(b"{}", Some(&text)).is_err());
+        assert!(expect_format(Some(&latin), Format::Json).is_err());
+        assert!(expect_format(Some(&text), Format::Text).is_ok());
+    }
+
+    #[test]
+    fn accept_preference() {
+        let accept = |s: &str| -> Accept {
+            ::hyper::header::Header::parse_header(&s.as_bytes().to_vec().into()).unwrap()
+        };
+        let formats = [Format::Protobuf, Format::Json];
+
+        assert_eq!(preferred_format(&accept("application/json"), &formats), Some(Format::Json));
+        assert_eq!(preferred_format(&accept("*/*"), &formats), Some(Format::Protobuf));
+        assert_eq!(
+            preferred_format(&accept("application/x-protobuf;q=0.5, application/json"), &formats),
+            Some(Format::Json)
+        );
+        assert_eq!(preferred_format(&accept("text/html"), &formats), None);
+    }
+}
@@ -1,3 +1,8 @@
+use std::fmt;
+use std::io;
+use std::error::Error as StdError;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
 use hyper::StatusCode;
 use hyper::header::ContentType;
 use responder::Responder;
@@ -6,43 +11,273 @@ use std::num::{ParseIntError, ParseFloatError};
 use std::string::ParseError as ParseStringError;
 use std::str::ParseBoolError;
 use hyper::Error as HyperError;
+use validate::ValidationErrors;
+use serde_json::{self, Value, Map};
+
+/// Domain error a handler can fail with, usually implemented by `#[derive(ResponseError)]`.
+///
+/// `Display` gives the `detail` of the error body.
+pub trait ResponseError: fmt::Debug + fmt::Display {
+    fn status(&self) -> u16;
+
+    fn code(&self) -> &'static str;
+}
 
 #[derive(Debug)]
 pub enum Error {
     RouterError,
+    NotFound,
     ParamParseError(String),
     ParamNotFound(&'static str),
+    QueryParseError(String),
+    HeaderNotFound(&'static str),
+    HeaderParseError(&'static str),
     HyperError(HyperError),
     FromDataError(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    ValidationFailed(ValidationErrors),
     StateNotFound(String),
+    Forbidden(String),
     OtherUsersFault(String),
     OtherServersFault(String),
+    App(Box<ResponseError>),
+    Io(io::Error),
+    Json(serde_json::Error),
+    Context(String, Box<Error>),
 }
 
 impl Error {
-    fn status_code(&self) -> StatusCode {
+    pub fn status_code(&self) -> StatusCode {
         match *self {
             Error::RouterError => StatusCode::NotFound,
+            Error::NotFound => StatusCode::NotFound,
             Error::ParamParseError(_) => StatusCode::BadRequest,
             Error::ParamNotFound(_) => StatusCode::BadRequest,
+            Error::QueryParseError(_) => StatusCode::BadRequest,
+            Error::HeaderNotFound(_) => StatusCode::BadRequest,
+            Error::HeaderParseError(_) => StatusCode::BadRequest,
             Error::HyperError(_) => StatusCode::BadRequest,
             Error::FromDataError(_) => StatusCode::UnprocessableEntity,
+            Error::PayloadTooLarge(_) => StatusCode::PayloadTooLarge,
+            Error::UnsupportedMediaType(_) => StatusCode::UnsupportedMediaType,
+            Error::ValidationFailed(_) => StatusCode::UnprocessableEntity,
             Error::StateNotFound(_) => StatusCode::InternalServerError,
+            Error::Forbidden(_) => StatusCode::Forbidden,
             Error::OtherUsersFault(_) => StatusCode::BadRequest,
             Error::OtherServersFault(_) => StatusCode::InternalServerError,
+            Error::App(ref e) => {
+                StatusCode::try_from(e.status()).unwrap_or(StatusCode::InternalServerError)
+            }
+            Error::Io(_) => StatusCode::InternalServerError,
+            Error::Json(_) => StatusCode::InternalServerError,
+            Error::Context(_, ref e) => e.status_code(),
         }
     }
 }
 
-impl Responder for Error {
-    fn respond(self) -> Response {
+impl Error {
+    /// Stable machine-readable code, sent as `code` in error bodies.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Error::RouterError => "route_not_found",
+            Error::NotFound => "not_found",
+            Error::ParamParseError(_) => "param_invalid",
+            Error::ParamNotFound(_) => "param_missing",
+            Error::QueryParseError(_) => "query_invalid",
+            Error::HeaderNotFound(_) => "header_missing",
+            Error::HeaderParseError(_) => "header_invalid",
+            Error::HyperError(_) => "bad_request",
+            Error::FromDataError(_) => "body_invalid",
+            Error::PayloadTooLarge(_) => "payload_too_large",
+            Error::UnsupportedMediaType(_) => "unsupported_media_type",
+            Error::ValidationFailed(_) => "validation_failed",
+            Error::StateNotFound(_) => "state_missing",
+            Error::Forbidden(_) => "forbidden",
+            Error::OtherUsersFault(_) => "bad_request",
+            Error::OtherServersFault(_) => "internal_error",
+            Error::App(ref e) => e.code(),
+            Error::Io(_) => "io_error",
+            Error::Json(_) => "json_error",
+            Error::Context(_, ref e) => e.code(),
+        }
+    }
+
+    /// Human-readable explanation including the errors underneath,
+    /// `None` when the title says it all.
+    pub(crate) fn detail(&self) -> Option<String> {
+        match *self {
+            Error::RouterError | Error::NotFound | Error::ValidationFailed(_) => None,
+            Error::ParamNotFound(name) => Some(format!("missing path param '{}'", name)),
+            Error::HeaderNotFound(name) => Some(format!("missing header '{}'", name)),
+            Error::HeaderParseError(name) => Some(format!("invalid header '{}'", name)),
+            Error::HyperError(ref e) => Some(e.to_string()),
+            Error::StateNotFound(ref ty) => Some(format!("no managed state of type {}", ty)),
+            Error::ParamParseError(ref s) |
+            Error::QueryParseError(ref s) |
+            Error::FromDataError(ref s) |
+            Error::PayloadTooLarge(ref s) |
+            Error::UnsupportedMediaType(ref s) |
+            Error::Forbidden(ref s) |
+            Error::OtherUsersFault(ref s) |
+            Error::OtherServersFault(ref s) => Some(s.clone()),
+            Error::App(ref e) => Some(e.to_string()),
+            Error::Io(ref e) => Some(e.to_string()),
+            Error::Json(ref e) => Some(e.to_string()),
+            Error::Context(ref c, ref e) => {
+                match e.detail() {
+                    Some(detail) => Some(format!("{}: {}", c, detail)),
+                    None => Some(c.clone()),
+                }
+            }
+        }
+    }
+
+    /// Wraps the error with a note on what was being done, status and code stay the same.
+    pub fn context<C: Into<String>>(self, context: C) -> Error {
+        Error::Context(context.into(), Box::new(self))
+    }
+
+    /// The innermost error under all contexts.
+    fn root(&self) -> &Error {
+        match *self {
+            Error::Context(_, ref e) => e.root(),
+            ref e => e,
+        }
+    }
+
+    /// RFC 7807 problem details of this error.
+    fn problem(&self, config: &ErrorConfig) -> Value {
+        let status = self.status_code();
+        let mut body = Map::new();
+
+        body.insert("type".to_string(), Value::String("about:blank".to_string()));
+        body.insert(
+            "title".to_string(),
+            Value::String(status.canonical_reason().unwrap_or("Error").to_string()),
+        );
+        body.insert("status".to_string(), Value::from(status.as_u16()));
+        body.insert("code".to_string(), Value::String(self.code().to_string()));
+
+        let internal = status.is_server_error() || match *self.root() {
+            Error::HyperError(_) => true,
+            _ => false,
+        };
+        if !(internal && config.production) {
+            if let Some(detail) = self.detail() {
+                body.insert("detail".to_string(), Value::String(detail));
+            }
+        }
+
+        match *self.root() {
+            Error::ParamNotFound(name) => {
+                body.insert("param".to_string(), Value::String(name.to_string()));
+            }
+            Error::HeaderNotFound(name) |
+            Error::HeaderParseError(name) => {
+                body.insert("header".to_string(), Value::String(name.to_string()));
+            }
+            Error::ValidationFailed(ref errors) => {
+                body.insert("errors".to_string(), validation_errors(errors));
+            }
+            _ => {}
+        }
+
+        Value::Object(body)
+    }
+}
+
+/// Shows only this error's own message, the wrapped ones are reachable
+/// through `source()`.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Context(ref c, _) => f.write_str(c),
+            Error::Io(_) => f.write_str("I/O error"),
+            Error::Json(_) => f.write_str("JSON error"),
+            Error::HyperError(_) => f.write_str("HTTP error"),
+            _ => {
+                match self.detail() {
+                    Some(detail) => f.write_str(&detail),
+                    None => f.write_str(self.status_code().canonical_reason().unwrap_or(self.code())),
+                }
+            }
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        self.code()
+    }
+
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        match *self {
+            Error::HyperError(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            Error::Json(ref e) => Some(e),
+            Error::Context(_, ref e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+/// `context` for results, e.g. `load(id).context("loading user")?`.
+pub trait ResultExt<T> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T, Error> {
+        self.map_err(|e| e.into().context(context))
+    }
+}
+
+/// How a server renders errors, each `Server` has its own.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ErrorConfig {
+    /// Omit the details of server-side errors, set through `Server::production`.
+    pub production: bool,
+}
+
+impl Error {
+    /// The problem+json response for this error under `config`.
+    pub(crate) fn render(self, config: &ErrorConfig) -> Response {
         Response::new()
             .with_status(self.status_code())
-            .with_header(ContentType::json())
-            .with_body(format!("{{'status': 'error', 'msg': '{:?}' }}", self))
+            .with_header(ContentType("application/problem+json".parse().unwrap()))
+            .with_body(self.problem(config).to_string())
+    }
+}
+
+/// Errors responded outside of a server get the development rendering,
+/// the server renders the ones handlers fail with using its own config.
+impl Responder for Error {
+    fn respond(self) -> Response {
+        self.render(&ErrorConfig::default())
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        Err(self)
     }
 }
 
+fn validation_errors(errors: &ValidationErrors) -> Value {
+    let fields = errors
+        .fields()
+        .iter()
+        .map(|&(ref field, ref e)| {
+            let mut m = Map::new();
+            m.insert("field".to_string(), Value::String(field.clone()));
+            m.insert("code".to_string(), Value::String(e.code.to_string()));
+            m.insert("message".to_string(), Value::String(e.message.clone()));
+            Value::Object(m)
+        })
+        .collect();
+
+    Value::Array(fields)
+}
+
 impl From<ParseIntError> for Error {
     fn from(e: ParseIntError) -> Error {
         Error::ParamParseError(e.to_string())
@@ -73,3 +308,116 @@ impl From<HyperError> for Error {
         Error::HyperError(e)
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// Serializing went wrong on our side, bodies failing to parse are
+/// mapped to `FromDataError` where they are read.
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(e: Utf8Error) -> Error {
+        Error::FromDataError(e.to_string())
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(e: FromUtf8Error) -> Error {
+        Error::FromDataError(e.to_string())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use futures::{Future, Stream};
+    use std::collections::HashMap;
+    use {FromData, Json, Responder};
+
+    fn body(e: Error) -> Value {
+        rendered(e, &ErrorConfig::default())
+    }
+
+    fn rendered(e: Error, config: &ErrorConfig) -> Value {
+        let chunk = e.render(config).body().concat2().wait().unwrap();
+        serde_json::from_slice(&chunk).unwrap()
+    }
+
+    #[test]
+    fn problem_json() {
+        let v = body(Error::HeaderNotFound("X-Api-Key"));
+        assert_eq!(v["status"], 400);
+        assert_eq!(v["code"], "header_missing");
+        assert_eq!(v["header"], "X-Api-Key");
+        assert_eq!(v["title"], "Bad Request");
+
+        let v = body(Error::FromDataError("expected `\"`".to_string()));
+        assert_eq!(v["detail"], "expected `\"`");
+
+        let v = body(Error::OtherServersFault("db password is hunter2".to_string()));
+        assert_eq!(v["code"], "internal_error");
+        assert!(v.get("detail").is_some());
+        let production = ErrorConfig { production: true };
+        let v = rendered(Error::OtherServersFault("db password is hunter2".to_string()), &production);
+        assert!(v.get("detail").is_none());
+        let v = rendered(Error::FromDataError("expected `\"`".to_string()), &production);
+        assert_eq!(v["detail"], "expected `\"`");
+    }
+
+    #[test]
+    fn serialize_failure_is_internal() {
+        let mut map = HashMap::new();
+        map.insert(vec![1u8], 1);
+
+        let e = Json(map).into_response().unwrap_err();
+        match e {
+            Error::OtherServersFault(_) => {}
+            _ => panic!("expected a server fault for a non-string map key"),
+        }
+        let v = rendered(e, &ErrorConfig { production: true });
+        assert_eq!(v["status"], 500);
+        assert!(v.get("detail").is_none());
+    }
+
+    #[test]
+    fn context_chain() {
+        let io = io::Error::new(io::ErrorKind::NotFound, "users.db missing");
+        let res: Result<(), io::Error> = Err(io);
+        let e = res.context("loading user 42").unwrap_err().context("GET /users/42");
+
+        assert_eq!(e.status_code(), StatusCode::InternalServerError);
+        assert_eq!(e.code(), "io_error");
+        assert_eq!(e.to_string(), "GET /users/42");
+
+        let inner = e.source().unwrap();
+        assert_eq!(inner.to_string(), "loading user 42");
+        let io = inner.source().unwrap();
+        assert_eq!(io.to_string(), "I/O error");
+        assert_eq!(io.source().unwrap().to_string(), "users.db missing");
+        assert_eq!(body(e)["detail"], "GET /users/42: loading user 42: users.db missing");
+
+        let e = Error::HeaderNotFound("X-Api-Key").context("auth");
+        assert_eq!(body(e)["header"], "X-Api-Key");
+    }
+
+    #[test]
+    fn json_errors() {
+        let e: Error = serde_json::from_str::<Value>("{").unwrap_err().into();
+        assert_eq!(e.status_code(), StatusCode::InternalServerError);
+        assert_eq!(e.to_string(), "JSON error");
+
+        let e = Json::<Value>::from_data(b"{", None).unwrap_err();
+        assert_eq!(e.status_code(), StatusCode::UnprocessableEntity);
+        assert_eq!(e.code(), "body_invalid");
+    }
+}
@@ -0,0 +1,38 @@
+use std::ops::Deref;
+
+use serde::de::DeserializeOwned;
+use hyper::mime::Mime;
+
+use {FromData, Format, Error, expect_format};
+use urlencoded;
+
+/// `application/x-www-form-urlencoded` body deserialized into `T`.
+///
+/// Supports repeated keys and nested keys like `address[city]=Oslo`.
+#[derive(Debug)]
+pub struct Form<T>(pub T);
+
+impl<T> Form<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned> FromData for Form<T> {
+    fn from_data(buf: &[u8], content_type: Option<&Mime>) -> Result<Self, Error> {
+        expect_format(content_type, Format::Form)?;
+        let inner = urlencoded::from_bytes(buf).map_err(|e| {
+            Error::FromDataError(e.to_string())
+        })?;
+
+        Ok(Form(inner))
+    }
+}
+
+impl<T> Deref for Form<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
@@ -0,0 +1,130 @@
+//! Serde body formats besides JSON, each behind its own cargo feature.
+
+macro_rules! serde_format {
+    ($(#[$attr:meta])* $feature:literal, $name:ident, $format:ident, $from:path, $to:path) => {
+        $(#[$attr])*
+        #[cfg(feature = $feature)]
+        #[derive(Debug)]
+        pub struct $name<T>(pub T);
+
+        #[cfg(feature = $feature)]
+        impl<T> $name<T> {
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl<T> ::std::ops::Deref for $name<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl<T: ::serde::de::DeserializeOwned> ::FromData for $name<T> {
+            fn from_data(buf: &[u8], content_type: Option<&::hyper::mime::Mime>) -> Result<Self, ::Error> {
+                ::expect_format(content_type, ::Format::$format)?;
+                let inner = $from(buf).map_err(|e| ::Error::FromDataError(e.to_string()))?;
+
+                Ok($name(inner))
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl<T: ::serde::Serialize> ::Responder for $name<T> {
+            fn respond(self) -> ::Response {
+                match self.into_response() {
+                    Ok(resp) => resp,
+                    Err(e) => e.respond(),
+                }
+            }
+
+            fn into_response(self) -> Result<::Response, ::Error> {
+                let body = $to(&self.0).map_err(|e| ::Error::OtherServersFault(e.to_string()))?;
+
+                Ok(::Response::new()
+                    .with_header(::hyper::header::ContentType(::Format::$format.mime()))
+                    .with_body(body))
+            }
+        }
+    }
+}
+
+serde_format!(
+    /// MessagePack body (`application/msgpack`), structs are encoded as maps.
+    "msgpack", MsgPack, MsgPack, ::rmp_serde::from_slice, ::rmp_serde::to_vec_named
+);
+
+serde_format!(
+    /// CBOR body (`application/cbor`).
+    "cbor", Cbor, Cbor, ::serde_cbor::from_slice, ::serde_cbor::to_vec
+);
+
+serde_format!(
+    /// YAML body (`application/yaml`).
+    "yaml", Yaml, Yaml, ::serde_yaml::from_slice, ::serde_yaml::to_vec
+);
+
+serde_format!(
+    /// TOML body (`application/toml`).
+    "toml", Toml, Toml, ::toml::from_slice, ::toml::to_vec
+);
+
+
+#[cfg(test)]
+mod tests {
+    #![allow(unused_imports, dead_code)]
+
+    use futures::{Future, Stream};
+    use hyper::header::ContentType;
+    use {Format, FromData, Negotiated, Responder, Response, Error};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Ping {
+        name: String,
+        count: u32,
+    }
+
+    fn ping() -> Ping {
+        Ping {
+            name: "a".to_string(),
+            count: 2,
+        }
+    }
+
+    fn body(resp: Response) -> Vec<u8> {
+        resp.body().concat2().wait().unwrap().to_vec()
+    }
+
+    macro_rules! roundtrip {
+        ($feature:literal, $test:ident, $name:ident, $format:ident) => {
+            #[cfg(feature = $feature)]
+            #[test]
+            fn $test() {
+                let mime = Format::$format.mime();
+                let resp = super::$name(ping()).respond();
+                assert_eq!(resp.headers().get::<ContentType>().unwrap().0, mime);
+
+                let bytes = body(resp);
+                assert_eq!(super::$name::<Ping>::from_data(&bytes, Some(&mime)).unwrap().0, ping());
+                assert_eq!(super::$name::<Ping>::from_data(&bytes, None).unwrap().0, ping());
+
+                match super::$name::<Ping>::from_data(&bytes, Some(&Format::Json.mime())) {
+                    Err(Error::UnsupportedMediaType(_)) => {}
+                    _ => panic!("expected 415 for a JSON content type"),
+                }
+
+                let n = Negotiated::<Ping>::from_data(&bytes, Some(&mime)).unwrap();
+                assert_eq!((n.format, n.value), (Format::$format, ping()));
+            }
+        }
+    }
+
+    roundtrip!("msgpack", msgpack_roundtrip, MsgPack, MsgPack);
+    roundtrip!("cbor", cbor_roundtrip, Cbor, Cbor);
+    roundtrip!("yaml", yaml_roundtrip, Yaml, Yaml);
+    roundtrip!("toml", toml_roundtrip, Toml, Toml);
+}
@@ -0,0 +1,84 @@
+use futures::{future, Future};
+use tokio_core::reactor::Handle;
+
+use {Request, Params, Error, Container, State};
+
+/// Resolves a handler argument from the request.
+///
+/// Every `#[handler]` argument that is neither a path segment nor the data argument
+/// goes through this trait, implement it for your own extractors.
+/// The request body is not available here, see `FromData` for that.
+pub trait FromRequest: Sized + 'static {
+    fn from_request(&Request, &Params, &Container) -> Box<Future<Item = Self, Error = Error>>;
+}
+
+impl<T: 'static> FromRequest for State<T> {
+    fn from_request(_: &Request, _: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        let res = state.get::<T>().ok_or_else(|| {
+            Error::StateNotFound(::std::any::type_name::<T>().to_string())
+        });
+
+        Box::new(future::result(res))
+    }
+}
+
+/// The reactor the server runs on, for spawning tasks and timers.
+impl FromRequest for Handle {
+    fn from_request(_: &Request, _: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        let res = state.get::<Handle>().map(|h| (*h).clone()).ok_or_else(|| {
+            Error::StateNotFound("Handle".to_string())
+        });
+
+        Box::new(future::result(res))
+    }
+}
+
+/// `None` when there is nothing to extract, e.g. a missing header.
+///
+/// Other errors, like a malformed header or missing state, still fail the request.
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(req: &Request, params: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        Box::new(T::from_request(req, params, state).then(|res| match res {
+            Ok(v) => Ok(Some(v)),
+            Err(Error::HeaderNotFound(_)) |
+            Err(Error::ParamNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Method, Request as HyperRequest};
+    use hyper::header::IfModifiedSince;
+    use Header;
+
+    fn extract(headers: &[(&'static str, &'static str)]) -> Result<Option<Header<IfModifiedSince>>, Error> {
+        let mut r = HyperRequest::new(Method::Get, "/".parse().unwrap());
+        for &(name, value) in headers {
+            r.headers_mut().set_raw(name, value);
+        }
+        Option::<Header<IfModifiedSince>>::from_request(&Request::new(r), &Params::new(), &Container::new())
+            .wait()
+    }
+
+    #[test]
+    fn optional_only_when_absent() {
+        assert!(extract(&[]).unwrap().is_none());
+        assert!(extract(&[("If-Modified-Since", "Sat, 29 Oct 1994 19:43:31 GMT")]).unwrap().is_some());
+
+        match extract(&[("If-Modified-Since", "yesterday")]) {
+            Err(Error::HeaderParseError(name)) => assert_eq!(name, "If-Modified-Since"),
+            other => panic!("expected a parse error, got {:?}", other.map(|h| h.is_some())),
+        }
+
+        let state = Option::<::State<u32>>::from_request(
+            &Request::new(HyperRequest::new(Method::Get, "/".parse().unwrap())),
+            &Params::new(),
+            &Container::new(),
+        ).wait();
+        assert!(state.is_err());
+    }
+}
@@ -7,4 +7,9 @@ pub trait Handler {
     fn handle(&self, Request, Params, Container) -> Box<Future<Item = Response, Error = Error>>;
     fn path(&self) -> &'static str;
     fn method(&self) -> Method;
+
+    /// Name used for reverse routing, unnamed handlers can't be looked up.
+    fn name(&self) -> &'static str {
+        ""
+    }
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+use std::ops::Deref;
+
+use futures::{future, Future};
+use hyper::header::{Header as HyperHeader, Headers};
+
+use {Request, Params, Error, Container};
+use from_request::FromRequest;
+
+/// Typed request header, responds with 400 if it's missing or malformed.
+///
+/// Use `Option<Header<H>>` for headers that may be absent.
+#[derive(Debug, Clone)]
+pub struct Header<H>(pub H);
+
+/// All request headers.
+#[derive(Debug, Clone)]
+pub struct HeaderMap(pub Headers);
+
+impl<H> Header<H> {
+    pub fn into_inner(self) -> H {
+        self.0
+    }
+}
+
+impl<H: HyperHeader + Clone> FromRequest for Header<H> {
+    fn from_request(req: &Request, _: &Params, _: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        let res = if req.headers().get_raw(H::header_name()).is_none() {
+            Err(Error::HeaderNotFound(H::header_name()))
+        } else {
+            req.headers().get::<H>().map(|h| Header(h.clone())).ok_or_else(|| {
+                Error::HeaderParseError(H::header_name())
+            })
+        };
+
+        Box::new(future::result(res))
+    }
+}
+
+impl<H> Deref for Header<H> {
+    type Target = H;
+
+    fn deref(&self) -> &H {
+        &self.0
+    }
+}
+
+impl HeaderMap {
+    pub fn into_inner(self) -> Headers {
+        self.0
+    }
+}
+
+impl FromRequest for HeaderMap {
+    fn from_request(req: &Request, _: &Params, _: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        Box::new(future::ok(HeaderMap(req.headers().clone())))
+    }
+}
+
+impl Deref for HeaderMap {
+    type Target = Headers;
+
+    fn deref(&self) -> &Headers {
+        &self.0
+    }
+}
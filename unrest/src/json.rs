@@ -1,8 +1,9 @@
 use serde_json::{self, Value};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use {FromData, Error, Responder, Response};
+use {FromData, Format, Error, Responder, Response, expect_format};
 use hyper::header::ContentType;
+use hyper::mime::Mime;
 
 #[derive(Debug)]
 pub struct Json<T = Value>(pub T);
@@ -14,10 +15,9 @@ impl<T> Json<T> {
 }
 
 impl<T: DeserializeOwned> FromData for Json<T> {
-    fn from_data(buf: &[u8]) -> Result<Self, Error> {
-        let inner = serde_json::from_slice(buf).map_err(|e| {
-            Error::FromDataError(e.to_string())
-        })?;
+    fn from_data(buf: &[u8], content_type: Option<&Mime>) -> Result<Self, Error> {
+        expect_format(content_type, Format::Json)?;
+        let inner = serde_json::from_slice(buf).map_err(|e| Error::FromDataError(e.to_string()))?;
 
         Ok(Json(inner))
     }
@@ -25,15 +25,16 @@ impl<T: DeserializeOwned> FromData for Json<T> {
 
 impl<T: Serialize> Responder for Json<T> {
     fn respond(self) -> Response {
-        match serde_json::to_vec(&self.0).map_err(|e| Error::OtherServersFault(e.to_string())) {
-            Ok(json) => {
-                Response::new().with_header(ContentType::json()).with_body(
-                    json,
-                )
-            }
+        match self.into_response() {
+            Ok(resp) => resp,
             Err(e) => e.respond(),
         }
+    }
 
-
+    fn into_response(self) -> Result<Response, Error> {
+        let json = serde_json::to_vec(&self.0).map_err(|e| {
+            Error::OtherServersFault(e.to_string())
+        })?;
+        Ok(Response::new().with_header(ContentType::json()).with_body(json))
     }
-}
\ No newline at end of file
+}
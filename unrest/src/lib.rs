@@ -2,12 +2,40 @@
 #![feature(proc_macro)]
 
 
+#[macro_use]
 extern crate serde;
 extern crate serde_json;
+#[macro_use]
 extern crate futures;
+extern crate futures_cpupool;
+extern crate bytes;
 extern crate route_recognizer;
 extern crate hyper;
 extern crate tokio_core;
+extern crate cookie;
+extern crate rand;
+extern crate url;
+extern crate digest;
+#[cfg(feature = "uuid")]
+extern crate uuid;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "cbor")]
+extern crate serde_cbor;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+#[cfg(feature = "toml")]
+extern crate toml;
+#[cfg(feature = "protobuf")]
+extern crate prost;
+#[cfg(feature = "regex")]
+extern crate regex;
+
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
 
 mod router;
 mod server;
@@ -17,18 +45,72 @@ mod handler;
 mod responder;
 mod errors;
 mod json;
+mod formats;
+#[cfg(feature = "protobuf")]
+mod protobuf;
 mod data;
 mod state;
+mod cookies;
+mod session;
+mod middleware;
+mod csrf;
+mod urlencoded;
+mod query;
+mod header;
+mod from_request;
+mod param;
+mod form;
+mod redirect;
+mod catcher;
+mod status;
+mod validate;
+mod multipart;
+mod body;
+mod static_files;
+mod streamed;
+mod sse;
 
 pub use router::Params;
 
 pub use server::Server;
-pub use router::Router;
-pub use request::Request;
+pub use router::{Router, Routes};
+pub use request::{Request, ResponseHook};
 pub use response::Response;
 pub use handler::Handler;
-pub use errors::Error;
+pub use middleware::Middleware;
+pub use errors::{Error, ResponseError, ResultExt};
 pub use responder::{Responder, LiftError};
 pub use json::Json;
-pub use data::{FromData, from_data_req};
-pub use state::{Container, State};
\ No newline at end of file
+#[cfg(feature = "msgpack")]
+pub use formats::MsgPack;
+#[cfg(feature = "cbor")]
+pub use formats::Cbor;
+#[cfg(feature = "yaml")]
+pub use formats::Yaml;
+#[cfg(feature = "toml")]
+pub use formats::Toml;
+#[cfg(feature = "protobuf")]
+pub use protobuf::Protobuf;
+#[cfg(feature = "protobuf-json")]
+pub use protobuf::ProtoJson;
+pub use query::{Query, query_param, HandlerArg, ArgFromQuery, ArgFromRequest};
+pub use form::Form;
+pub use redirect::Redirect;
+pub use catcher::{Catcher, Caught};
+pub use status::{Created, Accepted, NoContent, Custom};
+pub use validate::{Validate, Validated, ValidationError, ValidationErrors, rules};
+pub use header::{Header, HeaderMap};
+pub use from_request::FromRequest;
+pub use param::{FromParam, OptionalParam, parse_param, or_missing};
+pub use data::{FromData, FromBody, Format, Negotiated, from_data_req, expect_format, require_format,
+                preferred_format};
+pub use body::{BodyStream, NdJson, NDJSON_LINE_LIMIT};
+pub use static_files::{StaticFiles, NamedFile};
+pub use streamed::{Streamed, JsonArray, JsonLines};
+pub use sse::{EventStream, Event, LastEventId, SSE_KEEP_ALIVE};
+pub use multipart::{Multipart, Part, SavedFile, MultipartForm, MultipartFields, FromMultipart,
+                    MultipartField, TEXT_FIELD_LIMIT, FILE_FIELD_LIMIT};
+pub use state::{Container, State};
+pub use cookies::{Cookies, CookieKeys, Cookie, Key, SameSite};
+pub use session::{Session, Sessions, SessionStore, SessionData, MemoryStore, FileStore};
+pub use csrf::{Csrf, CsrfToken, CSRF_FORM_LIMIT};
\ No newline at end of file
@@ -0,0 +1,10 @@
+use futures::Future;
+use {Request, Error, Container};
+
+/// Runs before the request is routed.
+///
+/// A middleware can inspect or rewrite the request, register response hooks with
+/// `Request::on_response`, or reject the request by returning an error.
+pub trait Middleware {
+    fn handle(&self, Request, &Container) -> Box<Future<Item = Request, Error = Error>>;
+}
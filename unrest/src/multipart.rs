@@ -0,0 +1,561 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::env;
+use std::ops::Deref;
+
+use futures::{future, Future, Stream, Poll, Async};
+use hyper::{Body, Chunk};
+use hyper::header::{Headers, ContentType};
+use hyper::mime::{self, Mime};
+use rand::{self, Rng};
+
+use {Request, Error};
+use data::FromBody;
+use body::save_stream;
+
+const MAX_HEADERS_SIZE: usize = 8 * 1024;
+
+/// Size limit of a text field read by `MultipartForm`.
+pub const TEXT_FIELD_LIMIT: u64 = 64 * 1024;
+/// Size limit of a file saved by `MultipartForm`.
+pub const FILE_FIELD_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// `multipart/form-data` body, streamed part by part.
+///
+/// Each `Part` is a stream of chunks, a part that wasn't read to the end
+/// is skipped when the next one is requested.
+pub struct Multipart {
+    inner: Rc<RefCell<Inner>>,
+}
+
+pub struct Part {
+    inner: Rc<RefCell<Inner>>,
+    id: usize,
+    headers: Headers,
+    name: Option<String>,
+    filename: Option<String>,
+    content_type: Option<Mime>,
+}
+
+/// Part saved to a temporary file, the file is removed on drop unless persisted.
+#[derive(Debug)]
+pub struct SavedFile {
+    path: PathBuf,
+    size: u64,
+    filename: Option<String>,
+    content_type: Option<Mime>,
+    persisted: bool,
+}
+
+#[derive(PartialEq)]
+enum ParseState {
+    Preamble,
+    Boundary,
+    Headers,
+    Data,
+    Done,
+}
+
+struct Inner {
+    body: Body,
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    state: ParseState,
+    part: usize,
+}
+
+impl Multipart {
+    pub fn new(body: Body, boundary: &str) -> Multipart {
+        Multipart {
+            inner: Rc::new(RefCell::new(Inner {
+                body,
+                delimiter: format!("\r\n--{}", boundary).into_bytes(),
+                buf: b"\r\n".to_vec(),
+                state: ParseState::Preamble,
+                part: 0,
+            })),
+        }
+    }
+}
+
+impl FromBody for Multipart {
+    fn from_body(req: Request) -> Box<Future<Item = Self, Error = Error>> {
+        let boundary = match req.headers().get::<ContentType>() {
+            Some(ct) if ct.type_() == mime::MULTIPART && ct.subtype() == mime::FORM_DATA => {
+                ct.get_param(mime::BOUNDARY).map(|b| b.as_str().to_string())
+            }
+            _ => None,
+        };
+
+        match boundary {
+            Some(boundary) => Box::new(future::ok(Multipart::new(req.body(), &boundary))),
+            None => Box::new(future::err(Error::UnsupportedMediaType(
+                "expected multipart/form-data with a boundary".to_string(),
+            ))),
+        }
+    }
+}
+
+impl Stream for Multipart {
+    type Item = Part;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Part>, Error> {
+        let mut inner = self.inner.borrow_mut();
+        let headers = match try_ready!(inner.poll_part()) {
+            Some(headers) => headers,
+            None => return Ok(Async::Ready(None)),
+        };
+
+        let (name, filename) = match raw_header(&headers, "Content-Disposition") {
+            Some(cd) => (disposition_param(cd, "name"), disposition_param(cd, "filename")),
+            None => (None, None),
+        };
+        let content_type = raw_header(&headers, "Content-Type").and_then(|ct| ct.parse().ok());
+
+        Ok(Async::Ready(Some(Part {
+            inner: self.inner.clone(),
+            id: inner.part,
+            headers,
+            name,
+            filename,
+            content_type,
+        })))
+    }
+}
+
+impl Part {
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Form field name from `Content-Disposition`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.content_type.as_ref()
+    }
+
+    /// Writes the part to `path`, fails once more than `limit` bytes were received.
+    pub fn save_to<P: AsRef<Path>>(self, path: P, limit: u64) -> Box<Future<Item = u64, Error = Error>> {
+        save_stream(self, path.as_ref(), Some(limit))
+    }
+
+    /// Writes the part to a new file in the system temp directory.
+    pub fn save_temp(self, limit: u64) -> Box<Future<Item = SavedFile, Error = Error>> {
+        let name: String = rand::thread_rng().gen_ascii_chars().take(24).collect();
+        let path = env::temp_dir().join(format!("unrest-upload-{}", name));
+        let filename = self.filename.clone();
+        let content_type = self.content_type.clone();
+
+        let cleanup = path.clone();
+        let f = self.save_to(path.clone(), limit)
+            .map(move |size| {
+                SavedFile {
+                    path,
+                    size,
+                    filename,
+                    content_type,
+                    persisted: false,
+                }
+            })
+            .map_err(move |e| {
+                let _ = fs::remove_file(cleanup);
+                e
+            });
+
+        Box::new(f)
+    }
+
+    /// Reads the whole part as UTF-8 text.
+    pub fn text(self, limit: u64) -> Box<Future<Item = String, Error = Error>> {
+        let f = self.fold(Vec::new(), move |mut buf, chunk| {
+            if (buf.len() + chunk.len()) as u64 > limit {
+                return Err(Error::PayloadTooLarge(format!("part exceeds {} bytes", limit)));
+            }
+            buf.extend_from_slice(&chunk);
+            Ok(buf)
+        });
+
+        Box::new(f.and_then(|buf| {
+            String::from_utf8(buf).map_err(|e| Error::FromDataError(e.to_string()))
+        }))
+    }
+}
+
+impl Stream for Part {
+    type Item = Chunk;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, Error> {
+        self.inner.borrow_mut().poll_data(self.id)
+    }
+}
+
+impl SavedFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Filename sent by the client, don't use it as a path as is.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.content_type.as_ref()
+    }
+
+    /// Moves the file to `path` and keeps it.
+    pub fn persist<P: AsRef<Path>>(mut self, path: P) -> Result<(), Error> {
+        fs::rename(&self.path, path.as_ref())
+            .or_else(|_| {
+                fs::copy(&self.path, path.as_ref()).and_then(|_| fs::remove_file(&self.path))
+            })
+            .map_err(|e| Error::OtherServersFault(e.to_string()))?;
+
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for SavedFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Inner {
+    fn read_more(&mut self) -> Poll<(), Error> {
+        match self.body.poll()? {
+            Async::Ready(Some(chunk)) => {
+                self.buf.extend_from_slice(&chunk);
+                Ok(Async::Ready(()))
+            }
+            Async::Ready(None) => Err(Error::FromDataError(
+                "unexpected end of multipart body".to_string(),
+            )),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+
+    fn poll_part(&mut self) -> Poll<Option<Headers>, Error> {
+        loop {
+            match self.state {
+                ParseState::Preamble | ParseState::Data => {
+                    match find(&self.buf, &self.delimiter) {
+                        Some(i) => {
+                            self.buf.drain(..i + self.delimiter.len());
+                            self.state = ParseState::Boundary;
+                        }
+                        None => {
+                            let keep = self.delimiter.len();
+                            if self.buf.len() > keep {
+                                let n = self.buf.len() - keep;
+                                self.buf.drain(..n);
+                            }
+                            try_ready!(self.read_more());
+                        }
+                    }
+                }
+                ParseState::Boundary => {
+                    if self.buf.len() < 2 {
+                        try_ready!(self.read_more());
+                    } else if self.buf.starts_with(b"--") {
+                        self.state = ParseState::Done;
+                    } else if self.buf.starts_with(b"\r\n") {
+                        self.buf.drain(..2);
+                        self.state = ParseState::Headers;
+                    } else {
+                        return Err(Error::FromDataError("malformed multipart boundary".to_string()));
+                    }
+                }
+                ParseState::Headers => {
+                    let end = if self.buf.starts_with(b"\r\n") {
+                        Some((0, 2))
+                    } else {
+                        find(&self.buf, b"\r\n\r\n").map(|i| (i, 4))
+                    };
+
+                    match end {
+                        Some((i, skip)) => {
+                            let headers = parse_headers(&self.buf[..i])?;
+                            self.buf.drain(..i + skip);
+                            self.state = ParseState::Data;
+                            self.part += 1;
+                            return Ok(Async::Ready(Some(headers)));
+                        }
+                        None if self.buf.len() > MAX_HEADERS_SIZE => {
+                            return Err(Error::FromDataError(
+                                "multipart part headers are too large".to_string(),
+                            ));
+                        }
+                        None => try_ready!(self.read_more()),
+                    }
+                }
+                ParseState::Done => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+
+    fn poll_data(&mut self, id: usize) -> Poll<Option<Chunk>, Error> {
+        if id != self.part || self.state != ParseState::Data {
+            return Ok(Async::Ready(None));
+        }
+
+        loop {
+            match find(&self.buf, &self.delimiter) {
+                Some(i) => {
+                    let data: Vec<u8> = self.buf.drain(..i).collect();
+                    self.buf.drain(..self.delimiter.len());
+                    self.state = ParseState::Boundary;
+
+                    if data.is_empty() {
+                        return Ok(Async::Ready(None));
+                    }
+                    return Ok(Async::Ready(Some(data.into())));
+                }
+                None => {
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() > keep {
+                        let n = self.buf.len() - keep;
+                        let data: Vec<u8> = self.buf.drain(..n).collect();
+                        return Ok(Async::Ready(Some(data.into())));
+                    }
+                    try_ready!(self.read_more());
+                }
+            }
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parse_headers(buf: &[u8]) -> Result<Headers, Error> {
+    let s = ::std::str::from_utf8(buf).map_err(|e| Error::FromDataError(e.to_string()))?;
+    let mut headers = Headers::new();
+
+    for line in s.split("\r\n").filter(|l| !l.is_empty()) {
+        let mut kv = line.splitn(2, ':');
+        match (kv.next(), kv.next()) {
+            (Some(k), Some(v)) => headers.append_raw(k.trim().to_string(), v.trim().to_string()),
+            _ => {
+                return Err(Error::FromDataError(
+                    format!("malformed multipart header '{}'", line),
+                ))
+            }
+        }
+    }
+
+    Ok(headers)
+}
+
+fn raw_header<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers.get_raw(name).and_then(|raw| raw.one()).and_then(|v| {
+        ::std::str::from_utf8(v).ok()
+    })
+}
+
+/// Picks `name` out of `form-data; name="file"; filename="a.txt"`.
+fn disposition_param(cd: &str, name: &str) -> Option<String> {
+    cd.split(';').skip(1).filter_map(|p| {
+        let mut kv = p.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some(k), Some(v)) if k.trim().eq_ignore_ascii_case(name) => {
+                Some(v.trim().trim_matches('"').to_string())
+            }
+            _ => None,
+        }
+    }).next()
+}
+
+
+/// Struct built from all parts of a multipart body, see `#[derive(FromMultipart)]`.
+pub trait FromMultipart: Sized {
+    fn from_multipart(MultipartFields) -> Result<Self, Error>;
+}
+
+/// Type of a `FromMultipart` struct field.
+pub trait MultipartField: Sized {
+    fn from_field(name: &str, fields: &mut MultipartFields) -> Result<Self, Error>;
+}
+
+/// Text fields and uploaded files of a multipart body, by field name.
+#[derive(Debug, Default)]
+pub struct MultipartFields {
+    text: HashMap<String, Vec<String>>,
+    files: HashMap<String, Vec<SavedFile>>,
+}
+
+/// Multipart body read into `T`, text fields are kept in memory and files
+/// saved to temporary files, within `TEXT_FIELD_LIMIT` and `FILE_FIELD_LIMIT`.
+#[derive(Debug)]
+pub struct MultipartForm<T>(pub T);
+
+impl MultipartFields {
+    pub fn has(&self, name: &str) -> bool {
+        self.text.get(name).map_or(false, |v| !v.is_empty()) ||
+            self.files.get(name).map_or(false, |v| !v.is_empty())
+    }
+
+    pub fn take_text(&mut self, name: &str) -> Option<String> {
+        match self.text.get_mut(name) {
+            Some(v) if !v.is_empty() => Some(v.remove(0)),
+            _ => None,
+        }
+    }
+
+    pub fn take_file(&mut self, name: &str) -> Option<SavedFile> {
+        match self.files.get_mut(name) {
+            Some(v) if !v.is_empty() => Some(v.remove(0)),
+            _ => None,
+        }
+    }
+}
+
+impl<T> MultipartForm<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for MultipartForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: FromMultipart + 'static> FromBody for MultipartForm<T> {
+    fn from_body(req: Request) -> Box<Future<Item = Self, Error = Error>> {
+        let f = Multipart::from_body(req).and_then(|mp| {
+            mp.fold(MultipartFields::default(), |mut fields, part| {
+                let name = part.name().unwrap_or("").to_string();
+                let f: Box<Future<Item = MultipartFields, Error = Error>> = if part.filename().is_some() {
+                    Box::new(part.save_temp(FILE_FIELD_LIMIT).map(move |file| {
+                        fields.files.entry(name).or_insert_with(Vec::new).push(file);
+                        fields
+                    }))
+                } else {
+                    Box::new(part.text(TEXT_FIELD_LIMIT).map(move |text| {
+                        fields.text.entry(name).or_insert_with(Vec::new).push(text);
+                        fields
+                    }))
+                };
+                f
+            })
+        });
+
+        Box::new(f.and_then(T::from_multipart).map(MultipartForm))
+    }
+}
+
+impl MultipartField for String {
+    fn from_field(name: &str, fields: &mut MultipartFields) -> Result<Self, Error> {
+        fields.take_text(name).ok_or_else(|| {
+            Error::FromDataError(format!("missing field `{}`", name))
+        })
+    }
+}
+
+impl MultipartField for SavedFile {
+    fn from_field(name: &str, fields: &mut MultipartFields) -> Result<Self, Error> {
+        fields.take_file(name).ok_or_else(|| {
+            Error::FromDataError(format!("missing file `{}`", name))
+        })
+    }
+}
+
+impl<T: MultipartField> MultipartField for Option<T> {
+    fn from_field(name: &str, fields: &mut MultipartFields) -> Result<Self, Error> {
+        if fields.has(name) {
+            T::from_field(name, fields).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: MultipartField> MultipartField for Vec<T> {
+    fn from_field(name: &str, fields: &mut MultipartFields) -> Result<Self, Error> {
+        let mut out = Vec::new();
+        while fields.has(name) {
+            out.push(T::from_field(name, fields)?);
+        }
+        Ok(out)
+    }
+}
+
+macro_rules! multipart_field_via_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl MultipartField for $t {
+                fn from_field(name: &str, fields: &mut MultipartFields) -> Result<Self, Error> {
+                    let text = String::from_field(name, fields)?;
+                    text.trim().parse().map_err(|e| {
+                        Error::FromDataError(format!("invalid value '{}' for field `{}`: {}", text, name, e))
+                    })
+                }
+            }
+        )*
+    }
+}
+
+multipart_field_via_from_str!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, bool);
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts() {
+        let body = "preamble\r\n--XyZ\r\n\
+                    Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+                    hello\r\n--XyZ\r\n\
+                    Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+                    Content-Type: text/plain\r\n\r\n\
+                    line one\r\nline two\r\n--XyZ--\r\n";
+
+        let parts = Multipart::new(Body::from(body), "XyZ")
+            .and_then(|part| {
+                let meta = (
+                    part.name().map(|s| s.to_string()),
+                    part.filename().map(|s| s.to_string()),
+                    part.content_type().map(|m| m.to_string()),
+                );
+                part.text(1024).map(move |text| (meta, text))
+            })
+            .collect()
+            .wait()
+            .unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0], ((Some("title".to_string()), None, None), "hello".to_string()));
+        assert_eq!(
+            parts[1],
+            (
+                (Some("file".to_string()), Some("a.txt".to_string()), Some("text/plain".to_string())),
+                "line one\r\nline two".to_string(),
+            )
+        );
+    }
+}
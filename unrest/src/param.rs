@@ -0,0 +1,122 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use url::percent_encoding::percent_decode;
+
+use Error;
+
+/// Parses a path segment into a handler argument.
+///
+/// The segment is already percent-decoded. The error message ends up
+/// in the 400 response together with the segment name.
+pub trait FromParam: Sized {
+    fn from_param(param: &str) -> Result<Self, String>;
+}
+
+/// Handler argument filled from a path segment or a query parameter.
+///
+/// Implemented for every `FromParam` type and for `Option`s of them,
+/// which are `None` when the value is missing or empty.
+pub trait OptionalParam: Sized {
+    fn from_param(param: &str) -> Result<Self, String>;
+
+    /// Stands in for a missing or empty value, `None` if the value is required.
+    fn missing() -> Option<Self> {
+        None
+    }
+}
+
+impl<T: FromParam> OptionalParam for T {
+    fn from_param(param: &str) -> Result<Self, String> {
+        <T as FromParam>::from_param(param)
+    }
+}
+
+impl<T: FromParam> OptionalParam for Option<T> {
+    fn from_param(param: &str) -> Result<Self, String> {
+        <T as FromParam>::from_param(param).map(Some)
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+/// Decodes and parses the `name` segment, used by `#[handler]`.
+pub fn parse_param<T: OptionalParam>(name: &'static str, raw: &str) -> Result<T, Error> {
+    let decoded = percent_decode(raw.as_bytes()).decode_utf8().map_err(|e| {
+        Error::ParamParseError(format!("'{}': {}", name, e))
+    })?;
+
+    T::from_param(&decoded).map_err(|e| Error::ParamParseError(format!("'{}': {}", name, e)))
+}
+
+/// `value`, or the stand-in of `T` for a missing one, `missing` if `T` is required.
+pub fn or_missing<T: OptionalParam>(value: Option<T>, missing: Error) -> Result<T, Error> {
+    match value {
+        Some(value) => Ok(value),
+        None => T::missing().ok_or(missing),
+    }
+}
+
+macro_rules! from_param_via_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl FromParam for $t {
+                fn from_param(param: &str) -> Result<Self, String> {
+                    param.parse().map_err(|e| format!("{}", e))
+                }
+            }
+        )*
+    }
+}
+
+from_param_via_from_str!(
+    i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64, bool, char,
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr
+);
+
+impl FromParam for String {
+    fn from_param(param: &str) -> Result<Self, String> {
+        Ok(param.to_string())
+    }
+}
+
+#[cfg(feature = "uuid")]
+from_param_via_from_str!(::uuid::Uuid);
+
+#[cfg(feature = "chrono")]
+from_param_via_from_str!(
+    ::chrono::NaiveDate,
+    ::chrono::NaiveDateTime,
+    ::chrono::DateTime<::chrono::Utc>,
+    ::chrono::DateTime<::chrono::FixedOffset>
+);
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct UserId(u64);
+
+    impl FromParam for UserId {
+        fn from_param(param: &str) -> Result<Self, String> {
+            if !param.starts_with("u-") {
+                return Err("user ids start with 'u-'".to_string());
+            }
+            param[2..].parse().map(UserId).map_err(|_| "bad user id".to_string())
+        }
+    }
+
+    #[test]
+    fn decode_and_parse() {
+        assert_eq!(parse_param::<String>("name", "a%20b").unwrap(), "a b");
+        assert_eq!(parse_param::<u32>("id", "42").unwrap(), 42);
+        assert_eq!(parse_param::<UserId>("user", "u-7").unwrap().0, 7);
+
+        match parse_param::<UserId>("user", "7") {
+            Err(Error::ParamParseError(msg)) => assert_eq!(msg, "'user': user ids start with 'u-'"),
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
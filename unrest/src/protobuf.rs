@@ -0,0 +1,237 @@
+use std::ops::Deref;
+
+use prost::Message;
+use hyper::header::ContentType;
+use hyper::mime::Mime;
+#[cfg(feature = "protobuf-json")]
+use hyper::Headers;
+#[cfg(feature = "protobuf-json")]
+use hyper::header::Accept;
+#[cfg(feature = "protobuf-json")]
+use serde::Serialize;
+#[cfg(feature = "protobuf-json")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "protobuf-json")]
+use serde_json;
+
+use {FromData, Format, Error, Responder, Response, expect_format};
+#[cfg(feature = "protobuf-json")]
+use preferred_format;
+
+/// Protocol Buffers body (`application/x-protobuf`).
+///
+/// See `ProtoJson` for messages that are sent as JSON as well.
+#[derive(Debug)]
+pub struct Protobuf<T>(pub T);
+
+/// Protocol Buffers message that also speaks JSON, needs the `protobuf-json` feature.
+///
+/// The body is decoded according to its `Content-Type`. The response goes out in
+/// the format the client's `Accept` prefers, otherwise in the format the request
+/// body came in, protobuf by default.
+///
+/// JSON follows `T`'s serde impls. Generate them with `pbjson-build` to get the
+/// canonical proto3 JSON mapping (lowerCamelCase names, enums by name, 64-bit
+/// integers as strings), a plain `#[derive(Serialize)]` keeps the Rust field names.
+#[cfg(feature = "protobuf-json")]
+#[derive(Debug)]
+pub struct ProtoJson<T> {
+    value: T,
+    format: Format,
+}
+
+impl<T> Protobuf<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Protobuf<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Message + Default> FromData for Protobuf<T> {
+    fn from_data(buf: &[u8], content_type: Option<&Mime>) -> Result<Self, Error> {
+        expect_format(content_type, Format::Protobuf)?;
+        decode(buf).map(Protobuf)
+    }
+}
+
+impl<T: Message> Responder for Protobuf<T> {
+    fn respond(self) -> Response {
+        encode(&self.0)
+    }
+}
+
+#[cfg(feature = "protobuf-json")]
+impl<T> ProtoJson<T> {
+    pub fn new(value: T) -> ProtoJson<T> {
+        ProtoJson {
+            value,
+            format: Format::Protobuf,
+        }
+    }
+
+    /// Format the body came in, responses use it when `Accept` has no preference.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+#[cfg(feature = "protobuf-json")]
+impl<T> Deref for ProtoJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+#[cfg(feature = "protobuf-json")]
+impl<T: Message + Default + DeserializeOwned> FromData for ProtoJson<T> {
+    fn from_data(buf: &[u8], content_type: Option<&Mime>) -> Result<Self, Error> {
+        if content_type.and_then(Format::of) != Some(Format::Json) {
+            expect_format(content_type, Format::Protobuf)?;
+            return decode(buf).map(ProtoJson::new);
+        }
+
+        let value = serde_json::from_slice(buf).map_err(|e| {
+            Error::FromDataError(e.to_string())
+        })?;
+
+        Ok(ProtoJson {
+            value,
+            format: Format::Json,
+        })
+    }
+}
+
+#[cfg(feature = "protobuf-json")]
+impl<T: Message + Serialize> Responder for ProtoJson<T> {
+    fn respond(self) -> Response {
+        match self.into_response() {
+            Ok(resp) => resp,
+            Err(e) => e.respond(),
+        }
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        if self.format != Format::Json {
+            return Ok(encode(&self.value));
+        }
+
+        let json = serde_json::to_vec(&self.value).map_err(|e| {
+            Error::OtherServersFault(e.to_string())
+        })?;
+        Ok(Response::new().with_header(ContentType::json()).with_body(json))
+    }
+
+    fn negotiate(mut self, headers: &Headers) -> Result<Response, Error> {
+        let preferred = headers.get::<Accept>().and_then(|accept| {
+            preferred_format(accept, &[self.format, Format::Protobuf, Format::Json])
+        });
+        if let Some(format) = preferred {
+            self.format = format;
+        }
+
+        self.into_response()
+    }
+}
+
+fn decode<T: Message + Default>(buf: &[u8]) -> Result<T, Error> {
+    T::decode(buf).map_err(|e| Error::FromDataError(e.to_string()))
+}
+
+fn encode<T: Message>(value: &T) -> Response {
+    Response::new()
+        .with_header(ContentType(Format::Protobuf.mime()))
+        .with_body(value.encode_to_vec())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{Future, Stream};
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "protobuf-json", derive(Serialize, Deserialize))]
+    struct Ping {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(uint32, tag = "2")]
+        count: u32,
+    }
+
+    fn ping() -> Ping {
+        Ping {
+            name: "a".to_string(),
+            count: 2,
+        }
+    }
+
+    fn body(resp: Response) -> Vec<u8> {
+        resp.body().concat2().wait().unwrap().to_vec()
+    }
+
+    #[test]
+    fn protobuf_roundtrip() {
+        let proto = Format::Protobuf.mime();
+        let resp = Protobuf(ping()).respond();
+        assert_eq!(resp.headers().get::<ContentType>().unwrap().0, proto);
+
+        let bytes = body(resp);
+        assert_eq!(Protobuf::<Ping>::from_data(&bytes, Some(&proto)).unwrap().0, ping());
+        assert_eq!(Protobuf::<Ping>::from_data(&bytes, None).unwrap().0, ping());
+
+        match Protobuf::<Ping>::from_data(&bytes, Some(&Format::Json.mime())) {
+            Err(Error::UnsupportedMediaType(_)) => {}
+            _ => panic!("expected 415 for a JSON body"),
+        }
+    }
+
+    #[cfg(feature = "protobuf-json")]
+    #[test]
+    fn proto_json_negotiation() {
+        let json = Format::Json.mime();
+        let proto = Format::Protobuf.mime();
+        let content_type = |resp: &Response| resp.headers().get::<ContentType>().unwrap().0.clone();
+        let accept = |value: &'static str| {
+            let mut headers = Headers::new();
+            headers.set_raw("Accept", value);
+            headers
+        };
+
+        let from_json = ProtoJson::<Ping>::from_data(br#"{"name":"a","count":2}"#, Some(&json)).unwrap();
+        assert_eq!(from_json.format(), Format::Json);
+        assert_eq!(*from_json, ping());
+
+        let resp = from_json.negotiate(&Headers::new()).unwrap();
+        assert_eq!(content_type(&resp), json);
+        assert_eq!(body(resp), br#"{"name":"a","count":2}"#.to_vec());
+
+        let from_json = ProtoJson::<Ping>::from_data(br#"{"name":"a","count":2}"#, Some(&json)).unwrap();
+        let resp = from_json.negotiate(&accept("application/x-protobuf")).unwrap();
+        assert_eq!(content_type(&resp), proto);
+        assert_eq!(Protobuf::<Ping>::from_data(&body(resp), Some(&proto)).unwrap().0, ping());
+
+        let resp = ProtoJson::new(ping()).negotiate(&accept("*/*")).unwrap();
+        assert_eq!(content_type(&resp), proto);
+
+        let resp = ProtoJson::new(ping()).negotiate(&accept("application/json")).unwrap();
+        assert_eq!(content_type(&resp), json);
+
+        match ProtoJson::<Ping>::from_data(b"a=1", Some(&Format::Form.mime())) {
+            Err(Error::UnsupportedMediaType(_)) => {}
+            _ => panic!("expected 415 for a form body"),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use futures::{future, Future};
+use serde::de::DeserializeOwned;
+use url::form_urlencoded;
+
+use {Request, Params, Error, Container};
+use from_request::FromRequest;
+use param::{OptionalParam, or_missing};
+use urlencoded;
+
+/// Query string deserialized into `T`.
+///
+/// A plain extractor, take `q: Query<SearchParams>` as a handler argument.
+/// Repeated keys fill `Vec` fields, missing or empty values give `None` for `Option` fields.
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+impl<T> Query<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Query<T> {
+    fn from_request(req: &Request, _: &Params, _: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        let query = req.query().unwrap_or("");
+        let res = urlencoded::from_bytes(query.as_bytes())
+            .map(Query)
+            .map_err(|e| Error::QueryParseError(e.to_string()));
+
+        Box::new(future::result(res))
+    }
+}
+
+/// Parses the first `name` value of the query string, `None` if it's missing or empty.
+///
+/// `#[handler]` fills `FromParam` arguments that aren't path segments with it.
+pub fn query_param<T: OptionalParam>(req: &Request, name: &'static str) -> Result<Option<T>, Error> {
+    let query = req.query().unwrap_or("");
+    let value = form_urlencoded::parse(query.as_bytes())
+        .find(|&(ref k, _)| k == name)
+        .map(|(_, v)| v);
+
+    match value {
+        Some(ref v) if !v.is_empty() => {
+            T::from_param(v).map(Some).map_err(|e| {
+                Error::QueryParseError(format!("'{}': {}", name, e))
+            })
+        }
+        _ => Ok(None),
+    }
+}
+
+/// How `#[handler]` fills an argument that is neither a path segment nor the body.
+///
+/// `(&&HandlerArg::<T>::new()).resolve(..)` picks `ArgFromQuery` for `FromParam` types
+/// and their `Option`s, method lookup falls back to `ArgFromRequest` for the rest.
+#[doc(hidden)]
+pub struct HandlerArg<T>(PhantomData<T>);
+
+#[doc(hidden)]
+pub trait ArgFromQuery<T> {
+    fn resolve(&self, name: &'static str, req: &Request, params: &Params, state: &Container)
+        -> Box<Future<Item = T, Error = Error>>;
+}
+
+#[doc(hidden)]
+pub trait ArgFromRequest<T> {
+    fn resolve(&self, name: &'static str, req: &Request, params: &Params, state: &Container)
+        -> Box<Future<Item = T, Error = Error>>;
+}
+
+impl<T> HandlerArg<T> {
+    pub fn new() -> HandlerArg<T> {
+        HandlerArg(PhantomData)
+    }
+}
+
+impl<'a, T: OptionalParam + 'static> ArgFromQuery<T> for &'a HandlerArg<T> {
+    fn resolve(&self, name: &'static str, req: &Request, _: &Params, _: &Container)
+        -> Box<Future<Item = T, Error = Error>> {
+        let missing = Error::QueryParseError(format!("missing query parameter '{}'", name));
+        Box::new(future::result(query_param(req, name).and_then(|v| or_missing(v, missing))))
+    }
+}
+
+impl<T: FromRequest + 'static> ArgFromRequest<T> for HandlerArg<T> {
+    fn resolve(&self, _: &'static str, req: &Request, params: &Params, state: &Container)
+        -> Box<Future<Item = T, Error = Error>> {
+        T::from_request(req, params, state)
+    }
+}
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Method, Request as HyperRequest};
+
+    fn request(uri: &str) -> Request {
+        Request::new(HyperRequest::new(Method::Get, uri.parse().unwrap()))
+    }
+
+    /// What `#[handler(get("/list"), default(page = 1))]` expands to for `page: u32`.
+    fn page(req: &Request) -> Result<u32, Error> {
+        Ok(query_param::<u32>(req, "page")?.unwrap_or_else(|| 1))
+    }
+
+    #[test]
+    fn list_page_default() {
+        assert_eq!(page(&request("/list")).unwrap(), 1);
+        assert_eq!(page(&request("/list?page=")).unwrap(), 1);
+        assert_eq!(page(&request("/list?sort=name&page=3&page=4")).unwrap(), 3);
+
+        match page(&request("/list?page=abc")) {
+            Err(Error::QueryParseError(msg)) => assert!(msg.starts_with("'page': ")),
+            _ => panic!("expected a query error"),
+        }
+
+        let name = query_param::<String>(&request("/list?q=a+b%21"), "q").unwrap();
+        assert_eq!(name.unwrap(), "a b!");
+    }
+
+    #[test]
+    fn handler_args() {
+        let state = Container::new();
+        let req = request("/list?page=2&empty=");
+        // What `#[handler]` expands to for arguments without a default.
+        macro_rules! arg {
+            ($ty:ty, $name:expr) => {
+                (&&HandlerArg::<$ty>::new()).resolve($name, &req, &Params::new(), &state).wait()
+            }
+        }
+
+        assert_eq!(arg!(Option<u32>, "page").unwrap(), Some(2));
+        assert_eq!(arg!(Option<u32>, "empty").unwrap(), None);
+        assert_eq!(arg!(Option<u32>, "missing").unwrap(), None);
+        assert_eq!(arg!(u32, "page").unwrap(), 2);
+        match arg!(u32, "missing") {
+            Err(Error::QueryParseError(msg)) => assert!(msg.contains("'missing'")),
+            _ => panic!("expected a missing query parameter error"),
+        }
+
+        #[derive(Deserialize)]
+        struct Page {
+            page: u32,
+        }
+        assert_eq!(arg!(Query<Page>, "q").unwrap().page, 2);
+        match arg!(::State<String>, "config") {
+            Err(Error::StateNotFound(_)) => {}
+            _ => panic!("expected State to go through FromRequest"),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use hyper::StatusCode;
+use hyper::header::Location;
+
+use {Responder, Response};
+
+/// Redirect response.
+///
+/// Combine with `Routes::url_for` to redirect to a named handler.
+#[derive(Debug)]
+pub struct Redirect(StatusCode, String);
+
+impl Redirect {
+    /// 302 Found, browsers may change the method to GET.
+    pub fn to<U: Into<String>>(uri: U) -> Redirect {
+        Redirect(StatusCode::Found, uri.into())
+    }
+
+    /// 307 Temporary Redirect, method and body are kept.
+    pub fn temporary<U: Into<String>>(uri: U) -> Redirect {
+        Redirect(StatusCode::TemporaryRedirect, uri.into())
+    }
+
+    /// 308 Permanent Redirect, method and body are kept, clients may cache it.
+    pub fn permanent<U: Into<String>>(uri: U) -> Redirect {
+        Redirect(StatusCode::PermanentRedirect, uri.into())
+    }
+
+    /// 301 Moved Permanently, for old clients that don't know 308.
+    pub fn moved<U: Into<String>>(uri: U) -> Redirect {
+        Redirect(StatusCode::MovedPermanently, uri.into())
+    }
+
+    /// 303 See Other, the client follows up with a GET, use it after handling a POST.
+    pub fn see_other<U: Into<String>>(uri: U) -> Redirect {
+        Redirect(StatusCode::SeeOther, uri.into())
+    }
+}
+
+impl Responder for Redirect {
+    fn respond(self) -> Response {
+        Response::new().with_status(self.0).with_header(
+            Location::new(self.1),
+        )
+    }
+}
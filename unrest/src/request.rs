@@ -1,15 +1,45 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::any::{Any, TypeId};
+
+use futures::{future, Future};
 use hyper::Request as HyperRequest;
+use hyper::{Headers, Uri};
+
+use {Response, Error};
 
 pub use hyper::Method;
 pub use hyper::Body;
 
 pub struct Request {
     r: HyperRequest,
+    ext: Extensions,
+}
+
+/// Deferred work that runs against the response after the handler has produced it,
+/// e.g. writing cookie changes or persisting a session.
+pub trait ResponseHook {
+    fn on_response(&self, Response) -> Box<Future<Item = Response, Error = Error>>;
+}
+
+#[derive(Clone)]
+pub(crate) struct Extensions {
+    inner: Rc<RefCell<ExtensionsInner>>,
+}
+
+#[derive(Default)]
+struct ExtensionsInner {
+    locals: HashMap<TypeId, Box<Any>>,
+    hooks: Vec<Rc<ResponseHook>>,
 }
 
 impl Request {
     pub(crate) fn new(r: HyperRequest) -> Request {
-        Request { r }
+        Request {
+            r,
+            ext: Extensions { inner: Rc::new(RefCell::new(ExtensionsInner::default())) },
+        }
     }
 
     pub fn method(&self) -> &Method {
@@ -20,7 +50,68 @@ impl Request {
         self.r.path()
     }
 
+    pub fn uri(&self) -> &Uri {
+        self.r.uri()
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        self.r.query()
+    }
+
+    pub fn headers(&self) -> &Headers {
+        self.r.headers()
+    }
+
     pub fn body(self) -> Body {
         self.r.body()
     }
+
+    /// Takes the body out, leaving an empty one in its place.
+    pub fn take_body(&mut self) -> Body {
+        self.r.body_mut().take().unwrap_or_default()
+    }
+
+    pub fn set_body(&mut self, body: Body) {
+        self.r.set_body(body)
+    }
+
+    /// Returns a request-local value previously stored with `set_local`.
+    pub fn local<T: Clone + 'static>(&self) -> Option<T> {
+        self.ext
+            .inner
+            .borrow()
+            .locals
+            .get(&TypeId::of::<T>())
+            .and_then(|a| a.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Stores a value that lives as long as this request, replacing any previous one.
+    pub fn set_local<T: 'static>(&self, v: T) {
+        self.ext.inner.borrow_mut().locals.insert(
+            TypeId::of::<T>(),
+            Box::new(v),
+        );
+    }
+
+    /// Registers a hook to run once the response is ready.
+    /// Hooks run in reverse order of registration.
+    pub fn on_response<H: ResponseHook + 'static>(&self, hook: H) {
+        self.ext.inner.borrow_mut().hooks.push(Rc::new(hook));
+    }
+
+    pub(crate) fn extensions(&self) -> Extensions {
+        self.ext.clone()
+    }
+}
+
+impl Extensions {
+    pub(crate) fn run_hooks(self, resp: Response) -> Box<Future<Item = Response, Error = Error>> {
+        let hooks = self.inner.borrow().hooks.clone();
+
+        hooks.into_iter().rev().fold(
+            Box::new(future::ok(resp)),
+            |f, hook| Box::new(f.and_then(move |resp| hook.on_response(resp))),
+        )
+    }
 }
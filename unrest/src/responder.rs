@@ -2,10 +2,29 @@ use {Response, Error};
 use futures::Future;
 use futures::future::Then;
 
-use hyper::header::ContentType;
+use hyper::StatusCode;
+use hyper::header::{ContentType, Headers};
 
 pub trait Responder {
     fn respond(self) -> Response;
+
+    /// Like `respond`, but may fail with an `Error` that is then rendered by the
+    /// server, the same way as errors returned by handlers.
+    fn into_response(self) -> Result<Response, Error>
+    where
+        Self: Sized,
+    {
+        Ok(self.respond())
+    }
+
+    /// Like `into_response`, with the request headers at hand for content
+    /// negotiation. `#[handler]` responds through this.
+    fn negotiate(self, _: &Headers) -> Result<Response, Error>
+    where
+        Self: Sized,
+    {
+        self.into_response()
+    }
 }
 
 pub trait LiftError {
@@ -71,10 +90,96 @@ where
             Err(err) => err.respond(),
         }
     }
+
+    fn into_response(self) -> Result<Response, Error> {
+        match self {
+            Ok(ok) => ok.into_response(),
+            Err(err) => err.into_response(),
+        }
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        match self {
+            Ok(ok) => ok.negotiate(headers),
+            Err(err) => err.negotiate(headers),
+        }
+    }
 }
 
 impl Responder for Response {
     fn respond(self) -> Response {
         self
     }
-}
\ No newline at end of file
+}
+
+impl<T: Responder> Responder for (StatusCode, T) {
+    fn respond(self) -> Response {
+        self.1.respond().with_status(self.0)
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        Ok(self.1.into_response()?.with_status(self.0))
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        Ok(self.1.negotiate(headers)?.with_status(self.0))
+    }
+}
+
+/// Headers given here replace the ones set by `T`.
+impl<T: Responder> Responder for (StatusCode, Headers, T) {
+    fn respond(self) -> Response {
+        with_headers(self.2.respond().with_status(self.0), &self.1)
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        Ok(with_headers(self.2.into_response()?.with_status(self.0), &self.1))
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        Ok(with_headers(self.2.negotiate(headers)?.with_status(self.0), &self.1))
+    }
+}
+
+impl<T: Responder> Responder for (T, Headers) {
+    fn respond(self) -> Response {
+        with_headers(self.0.respond(), &self.1)
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        Ok(with_headers(self.0.into_response()?, &self.1))
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        Ok(with_headers(self.0.negotiate(headers)?, &self.1))
+    }
+}
+
+fn with_headers(mut resp: Response, headers: &Headers) -> Response {
+    resp.headers_mut().extend(headers.iter());
+    resp
+}
+
+/// `None` is a 404 `Error::NotFound`.
+impl<T: Responder> Responder for Option<T> {
+    fn respond(self) -> Response {
+        match self {
+            Some(v) => v.respond(),
+            None => Error::NotFound.respond(),
+        }
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        match self {
+            Some(v) => v.into_response(),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        match self {
+            Some(v) => v.negotiate(headers),
+            None => Err(Error::NotFound),
+        }
+    }
+}
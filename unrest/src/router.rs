@@ -1,8 +1,11 @@
 use std::rc::Rc;
 use std::collections::HashMap;
-use futures::Future;
+use futures::{future, Future};
 use route_recognizer;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET, DEFAULT_ENCODE_SET};
 use state::Container;
+use from_request::FromRequest;
+use catcher::{Catcher, Catchers};
 
 use request::Method;
 use super::{Handler, Request, Response, Error};
@@ -11,30 +14,76 @@ pub use route_recognizer::Params;
 
 #[derive(Clone)]
 pub struct Router {
-    rr: Rc<HashMap<Method, route_recognizer::Router<Box<Handler + 'static>>>>,
+    rr: Rc<HashMap<Method, route_recognizer::Router<Rc<Handler + 'static>>>>,
+    routes: Routes,
+    catchers: Catchers,
+}
+
+/// Paths of named handlers, for building URLs from handler names.
+///
+/// Handlers generated by `#[handler]` are named after their function.
+/// Available to handlers as an argument once the server runs.
+#[derive(Clone, Debug, Default)]
+pub struct Routes {
+    paths: HashMap<String, String>,
 }
 
 impl Router {
     pub fn new() -> Router {
-        Router { rr: Rc::new(HashMap::new()) }
+        Router {
+            rr: Rc::new(HashMap::new()),
+            routes: Routes::default(),
+            catchers: Catchers::default(),
+        }
     }
 
     pub fn get(&mut self, route: &str, handler: Box<Handler + 'static>) {
-        let mut rr = Rc::get_mut(&mut self.rr).expect("can't modify router at this point");
-        rr.entry(Method::Get)
-            .or_insert_with(route_recognizer::Router::new)
-            .add(route, handler);
+        if !handler.name().is_empty() {
+            self.routes.paths.insert(handler.name().to_string(), route.to_string());
+        }
+        self.add(Method::Get, route, handler);
     }
 
     pub fn mount(&mut self, prefix: &str, handler: Box<Handler + 'static>) {
-        let mut rr = Rc::get_mut(&mut self.rr).expect("can't modify router at this point");
         let prefix = if prefix == "/" { "" } else { prefix };
 
         let path = format!("{}{}", prefix, handler.path());
+        let method = handler.method();
+
+        if !handler.name().is_empty() {
+            self.routes.paths.insert(handler.name().to_string(), path.clone());
+        }
+        self.add(method, &path, handler);
+    }
 
-        rr.entry(handler.method())
-            .or_insert_with(route_recognizer::Router::new)
-            .add(&path, handler);
+    /// Renders errors and error responses with `status` through `catcher`.
+    pub fn catch<C: Catcher + 'static>(&mut self, status: u16, catcher: C) {
+        self.catchers.add(status, Rc::new(catcher));
+    }
+
+    /// Registers a catcher generated by `#[catcher(..)]` for its status.
+    pub fn register_catcher(&mut self, catcher: Box<Catcher + 'static>) {
+        let status = catcher.status().expect("catcher without a status, use Router::catch");
+        self.catchers.add(status, catcher.into());
+    }
+
+    pub(crate) fn catchers(&self) -> &Catchers {
+        &self.catchers
+    }
+
+    pub fn routes(&self) -> Routes {
+        self.routes.clone()
+    }
+
+    /// Optional groups like `/items(/:page)` register every variant of the route.
+    fn add(&mut self, method: Method, route: &str, handler: Box<Handler + 'static>) {
+        let mut rr = Rc::get_mut(&mut self.rr).expect("can't modify router at this point");
+        let handler: Rc<Handler + 'static> = handler.into();
+        let rr = rr.entry(method).or_insert_with(route_recognizer::Router::new);
+
+        for route in expand_optional(route) {
+            rr.add(&route, handler.clone());
+        }
     }
 
     pub fn run(
@@ -47,4 +96,111 @@ impl Router {
         let m = rr.recognize(req.path()).map_err(|_| Error::RouterError)?;
         Ok(m.handler.handle(req, m.params, state))
     }
-}
\ No newline at end of file
+}
+
+impl Routes {
+    /// Builds the path of handler `name`, filling `:segment`s from `params`.
+    ///
+    /// Optional groups are kept only when all their segments are given.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, Error> {
+        let route = self.paths.get(name).ok_or_else(|| {
+            Error::OtherServersFault(format!("no route named '{}'", name))
+        })?;
+
+        expand_optional(route)
+            .iter()
+            .rev()
+            .filter_map(|variant| fill_segments(variant, params))
+            .next()
+            .ok_or_else(|| {
+                Error::OtherServersFault(format!("missing params for route '{}'", name))
+            })
+    }
+}
+
+impl FromRequest for Routes {
+    fn from_request(_: &Request, _: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        let routes = state.get::<Routes>().map(|r| (*r).clone()).ok_or_else(|| {
+            Error::StateNotFound("Routes".to_string())
+        });
+        Box::new(future::result(routes))
+    }
+}
+
+fn fill_segments(route: &str, params: &[(&str, &str)]) -> Option<String> {
+    let find = |name: &str| params.iter().find(|&&(k, _)| k == name).map(|&(_, v)| v);
+
+    let segments: Option<Vec<String>> = route
+        .split('/')
+        .map(|seg| if seg.starts_with(':') {
+            find(&seg[1..]).map(|v| utf8_percent_encode(v, PATH_SEGMENT_ENCODE_SET).to_string())
+        } else if seg.starts_with('*') {
+            find(&seg[1..]).map(|v| utf8_percent_encode(v, DEFAULT_ENCODE_SET).to_string())
+        } else {
+            Some(seg.to_string())
+        })
+        .collect();
+
+    segments.map(|s| s.join("/"))
+}
+
+fn expand_optional(route: &str) -> Vec<String> {
+    let start = match route.find('(') {
+        Some(start) => start,
+        None => return vec![route.to_string()],
+    };
+
+    let mut depth = 0;
+    let mut end = None;
+    for (i, c) in route[start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(start + i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let end = end.expect("unbalanced parentheses in route");
+
+    let (prefix, inner, rest) = (&route[..start], &route[start + 1..end], &route[end + 1..]);
+    let mut out = expand_optional(&format!("{}{}", prefix, rest));
+    out.extend(expand_optional(&format!("{}{}{}", prefix, inner, rest)));
+    out
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optional_groups() {
+        assert_eq!(expand_optional("/items"), vec!["/items"]);
+        assert_eq!(expand_optional("/items(/:page)"), vec!["/items", "/items/:page"]);
+        assert_eq!(
+            expand_optional("/a(/:b(/:c))/d"),
+            vec!["/a/d", "/a/:b/d", "/a/:b/:c/d"]
+        );
+    }
+
+    #[test]
+    fn url_for() {
+        let mut routes = Routes::default();
+        routes.paths.insert("list".to_string(), "/users(/:page)".to_string());
+        routes.paths.insert("show".to_string(), "/users/:id/files/*path".to_string());
+
+        assert_eq!(routes.url_for("list", &[]).unwrap(), "/users");
+        assert_eq!(routes.url_for("list", &[("page", "2")]).unwrap(), "/users/2");
+        assert_eq!(
+            routes.url_for("show", &[("id", "a b"), ("path", "x/y.txt")]).unwrap(),
+            "/users/a%20b/files/x/y.txt"
+        );
+        assert!(routes.url_for("show", &[("id", "1")]).is_err());
+        assert!(routes.url_for("nope", &[]).is_err());
+    }
+}
@@ -1,28 +1,38 @@
 use std::io::Error as IoError;
+use std::rc::Rc;
 use std::net::SocketAddr;
 
 use tokio_core::reactor::{Handle, Core};
 use tokio_core::net::TcpListener;
 
-use futures::{Future, Stream, IntoFuture};
+use futures::{future, Future, Stream, IntoFuture};
 
 use hyper::server::{Http, Request, Response, Service};
 use hyper;
 
 use state::Container;
+use cookies::CookieKeys;
 
-use responder::Responder;
-use router::Router;
+use router::{Router, Routes};
+use middleware::Middleware;
+use errors::{Error, ErrorConfig};
+use catcher::{Catcher, Catchers, RequestHead};
 
 pub struct Server {
     listener: TcpListener,
     router: Router,
     state: Container,
+    middleware: Vec<Rc<Middleware>>,
+    catchers: Catchers,
+    errors: ErrorConfig,
 }
 
 struct S {
     router: Router,
     state: Container,
+    middleware: Vec<Rc<Middleware>>,
+    catchers: Catchers,
+    errors: ErrorConfig,
 }
 
 impl Service for S {
@@ -45,6 +55,9 @@ impl Server {
             listener,
             router,
             state,
+            middleware: Vec::new(),
+            catchers: Catchers::default(),
+            errors: ErrorConfig::default(),
         }
     }
 
@@ -56,15 +69,50 @@ impl Server {
         self
     }
 
+    /// Leaves the details of server-side errors out of error responses.
+    pub fn production(mut self, on: bool) -> Server {
+        self.errors.production = on;
+        self
+    }
+
+    /// Like `Router::catch`, catchers set here win over the router's.
+    pub fn catch<C: Catcher + 'static>(mut self, status: u16, catcher: C) -> Server {
+        self.catchers.add(status, Rc::new(catcher));
+        self
+    }
+
+    /// Adds a middleware, they run in the order they were added.
+    pub fn middleware<M: Middleware + 'static>(mut self, m: M) -> Server {
+        self.middleware.push(Rc::new(m));
+        self
+    }
+
     pub fn run(self, handle: Handle) -> impl Future<Item = (), Error = IoError> {
         let http = Http::new();
         let router = self.router;
-        let state = self.state;
+        let middleware = self.middleware;
+        let errors = self.errors;
+        let mut state = self.state;
+        let mut catchers = router.catchers().clone();
+        catchers.merge(&self.catchers);
+
+        if state.get::<CookieKeys>().is_none() {
+            state.set(CookieKeys::generate());
+        }
+        if state.get::<Routes>().is_none() {
+            state.set(router.routes());
+        }
+        if state.get::<Handle>().is_none() {
+            state.set(handle.clone());
+        }
 
         let service_factory = move || {
             S {
                 router: router.clone(),
                 state: state.clone(),
+                middleware: middleware.clone(),
+                catchers: catchers.clone(),
+                errors,
             }
         };
 
@@ -96,15 +144,48 @@ impl S {
     fn serve(&self, hreq: Request) -> Box<Future<Item = Response, Error = hyper::Error>> {
         use super::Request as RRequest;
 
-        let f = self.router
-            .run(RRequest::new(hreq), self.state.clone())
-            .into_future()
-            .flatten();
-
-        let f = f.then(|r| match r {
+        let req = RRequest::new(hreq);
+        let ext = req.extensions();
+        let head = if self.catchers.is_empty() {
+            None
+        } else {
+            Some(RequestHead {
+                method: req.method().clone(),
+                uri: req.uri().clone(),
+                headers: req.headers().clone(),
+            })
+        };
+        let router = self.router.clone();
+        let state = self.state.clone();
+
+        let f = self.middleware.iter().fold(
+            Box::new(future::ok(req)) as Box<Future<Item = RRequest, Error = Error>>,
+            |f, m| {
+                let m = m.clone();
+                let state = state.clone();
+                Box::new(f.and_then(move |req| m.handle(req, &state)))
+            },
+        );
+
+        let f = f.and_then(move |req| router.run(req, state).into_future().flatten());
+
+        let catchers = self.catchers.clone();
+        let errors = self.errors;
+        let f = f.then(move |r| -> Box<Future<Item = Response, Error = Error>> {
+            match (head, r) {
+                (Some(head), r) => catchers.run(&head, r, &errors),
+                (None, Ok(r)) => Box::new(future::ok(r)),
+                (None, Err(e)) => Box::new(future::ok(e.render(&errors))),
+            }
+        }).then(move |r| match r {
             Ok(r) => Ok(r),
-            Err(e) => Ok(e.respond()),
-        });
+            Err(e) => Ok(e.render(&errors)),
+        })
+            .and_then(move |r| ext.run_hooks(r))
+            .then(move |r| match r {
+                Ok(r) => Ok(r),
+                Err(e) => Ok(e.render(&errors)),
+            });
 
         Box::new(f)
     }
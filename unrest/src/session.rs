@@ -0,0 +1,506 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::io::{Read, Write, ErrorKind};
+
+use futures::{future, Future};
+use rand::{self, Rng};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value, Map};
+
+use {Request, Response, Params, Error, Container, State, Cookies, Cookie, SameSite};
+use request::ResponseHook;
+use body::file_pool;
+use from_request::FromRequest;
+
+pub type SessionData = HashMap<String, Value>;
+
+/// Backend that keeps session data between requests.
+///
+/// Implementations must treat expired sessions as missing.
+pub trait SessionStore {
+    fn load(&self, id: &str) -> Box<Future<Item = Option<SessionData>, Error = Error>>;
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Box<Future<Item = (), Error = Error>>;
+    fn destroy(&self, id: &str) -> Box<Future<Item = (), Error = Error>>;
+}
+
+/// Session configuration, managed as server state.
+pub struct Sessions {
+    store: Box<SessionStore>,
+    cookie_name: String,
+    ttl: Duration,
+}
+
+impl Sessions {
+    pub fn new<S: SessionStore + 'static>(store: S) -> Sessions {
+        Sessions {
+            store: Box::new(store),
+            cookie_name: "unrest_session".to_string(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    pub fn cookie_name(mut self, name: &str) -> Sessions {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Sessions {
+        self.ttl = ttl;
+        self
+    }
+}
+
+/// Session of the current request.
+///
+/// Loaded from the store when the request starts, written back only if it was changed.
+#[derive(Clone)]
+pub struct Session {
+    inner: Rc<RefCell<SessionInner>>,
+}
+
+struct SessionInner {
+    id: Option<String>,
+    data: SessionData,
+    dirty: bool,
+    regenerate: bool,
+    destroyed: bool,
+}
+
+struct PersistSession {
+    session: Session,
+    sessions: State<Sessions>,
+    cookies: Cookies,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session {
+            inner: Rc::new(RefCell::new(SessionInner {
+                id: None,
+                data: SessionData::new(),
+                dirty: false,
+                regenerate: false,
+                destroyed: false,
+            })),
+        }
+    }
+
+    /// Returns the session of `req`, all calls within one request share it.
+    pub fn for_request(req: &Request, state: &Container) -> Box<Future<Item = Session, Error = Error>> {
+        if let Some(session) = req.local::<Session>() {
+            return Box::new(future::ok(session));
+        }
+
+        let sessions = match state.get::<Sessions>() {
+            Some(sessions) => sessions,
+            None => return Box::new(future::err(Error::StateNotFound("Sessions".to_string()))),
+        };
+        let cookies = match Cookies::for_request(req, state) {
+            Ok(cookies) => cookies,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let id = cookies.get_private(&sessions.cookie_name).map(|c| {
+            c.value().to_string()
+        });
+        let load = id.as_ref().map(|id| sessions.store.load(id));
+
+        let session = Session::new();
+        req.set_local(session.clone());
+        req.on_response(PersistSession {
+            session: session.clone(),
+            sessions,
+            cookies,
+        });
+
+        match (id, load) {
+            (Some(id), Some(load)) => Box::new(load.map(move |data| {
+                if let Some(data) = data {
+                    let mut inner = session.inner.borrow_mut();
+                    inner.id = Some(id);
+                    inner.data = data;
+                }
+                session
+            })),
+            _ => Box::new(future::ok(session)),
+        }
+    }
+
+    pub fn id(&self) -> Option<String> {
+        self.inner.borrow().id.clone()
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.inner.borrow().data.get(key).and_then(|v| {
+            serde_json::from_value(v.clone()).ok()
+        })
+    }
+
+    pub fn set<T: Serialize>(&self, key: &str, value: T) -> Result<(), Error> {
+        let value = serde_json::to_value(value).map_err(|e| {
+            Error::OtherServersFault(e.to_string())
+        })?;
+
+        let mut inner = self.inner.borrow_mut();
+        inner.data.insert(key.to_string(), value);
+        inner.dirty = true;
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.data.remove(key).is_some() {
+            inner.dirty = true;
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.data.clear();
+        inner.dirty = true;
+    }
+
+    /// Moves the data to a fresh session id, call it on login
+    /// so a session id known before authentication becomes useless.
+    pub fn regenerate(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.regenerate = true;
+        inner.dirty = true;
+    }
+
+    /// Removes the session from the store and the client.
+    pub fn destroy(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.data.clear();
+        inner.destroyed = true;
+    }
+}
+
+impl FromRequest for Session {
+    fn from_request(req: &Request, _: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        Session::for_request(req, state)
+    }
+}
+
+fn new_session_id() -> String {
+    rand::thread_rng().gen_ascii_chars().take(32).collect()
+}
+
+impl ResponseHook for PersistSession {
+    fn on_response(&self, resp: Response) -> Box<Future<Item = Response, Error = Error>> {
+        let mut inner = self.session.inner.borrow_mut();
+        let store = &self.sessions.store;
+        let cookie_name = self.sessions.cookie_name.clone();
+
+        if inner.destroyed {
+            self.cookies.remove_private(Cookie::build(cookie_name, "").path("/").finish());
+            return match inner.id.take() {
+                Some(id) => Box::new(store.destroy(&id).map(move |_| resp)),
+                None => Box::new(future::ok(resp)),
+            };
+        }
+
+        if !inner.dirty {
+            return Box::new(future::ok(resp));
+        }
+
+        let old_id = if inner.regenerate { inner.id.take() } else { None };
+        let id = match inner.id.clone() {
+            Some(id) => id,
+            None => {
+                let id = new_session_id();
+                self.cookies.add_private(
+                    Cookie::build(cookie_name, id.clone())
+                        .path("/")
+                        .http_only(true)
+                        .same_site(SameSite::Lax)
+                        .finish(),
+                );
+                inner.id = Some(id.clone());
+                id
+            }
+        };
+
+        let saved = store.save(&id, &inner.data, self.sessions.ttl);
+        match old_id {
+            Some(old_id) => Box::new(store.destroy(&old_id).join(saved).map(move |_| resp)),
+            None => Box::new(saved.map(move |_| resp)),
+        }
+    }
+}
+
+
+/// In-process store, sessions are lost on restart.
+pub struct MemoryStore {
+    sessions: RefCell<HashMap<String, (Instant, SessionData)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore { sessions: RefCell::new(HashMap::new()) }
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Box<Future<Item = Option<SessionData>, Error = Error>> {
+        let mut sessions = self.sessions.borrow_mut();
+        let expired = match sessions.get(id) {
+            Some(&(expires, _)) => expires <= Instant::now(),
+            None => return Box::new(future::ok(None)),
+        };
+
+        if expired {
+            sessions.remove(id);
+            return Box::new(future::ok(None));
+        }
+
+        Box::new(future::ok(sessions.get(id).map(|&(_, ref data)| data.clone())))
+    }
+
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Box<Future<Item = (), Error = Error>> {
+        let now = Instant::now();
+        let mut sessions = self.sessions.borrow_mut();
+        sessions.retain(|_, &mut (expires, _)| expires > now);
+        sessions.insert(id.to_string(), (now + ttl, data.clone()));
+
+        Box::new(future::ok(()))
+    }
+
+    fn destroy(&self, id: &str) -> Box<Future<Item = (), Error = Error>> {
+        self.sessions.borrow_mut().remove(id);
+        Box::new(future::ok(()))
+    }
+}
+
+
+/// Keeps every session as a json file in `dir`, the files are read and
+/// written on the file pool.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> FileStore {
+        FileStore { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, Error> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(Error::OtherUsersFault("invalid session id".to_string()));
+        }
+
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> Box<Future<Item = Option<SessionData>, Error = Error>> {
+        let path = match self.path(id) {
+            Ok(path) => path,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        Box::new(file_pool().spawn_fn(move || read_session(&path)).map_err(
+            Error::OtherServersFault,
+        ))
+    }
+
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Box<Future<Item = (), Error = Error>> {
+        let path = match self.path(id) {
+            Ok(path) => path,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let dir = self.dir.clone();
+        let data = data.clone();
+
+        Box::new(file_pool().spawn_fn(move || write_session(&dir, &path, data, ttl)).map_err(
+            Error::OtherServersFault,
+        ))
+    }
+
+    fn destroy(&self, id: &str) -> Box<Future<Item = (), Error = Error>> {
+        let path = match self.path(id) {
+            Ok(path) => path,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        let removed = file_pool().spawn_fn(move || match fs::remove_file(path) {
+            Ok(_) => Ok(()),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.to_string()),
+        });
+        Box::new(removed.map_err(Error::OtherServersFault))
+    }
+}
+
+/// Runs on the file pool, errors are sent back as messages since `Error` isn't `Send`.
+fn read_session(path: &Path) -> Result<Option<SessionData>, String> {
+    let mut buf = Vec::new();
+    match fs::File::open(path) {
+        Ok(mut f) => f.read_to_end(&mut buf).map_err(|e| e.to_string())?,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut stored: Map<String, Value> = serde_json::from_slice(&buf).map_err(|e| e.to_string())?;
+
+    let expires = stored.get("expires").and_then(|v| v.as_u64()).unwrap_or(0);
+    if expires <= unix_now() {
+        let _ = fs::remove_file(path);
+        return Ok(None);
+    }
+
+    match stored.remove("data") {
+        Some(Value::Object(data)) => Ok(Some(data.into_iter().collect())),
+        _ => Ok(None),
+    }
+}
+
+fn write_session(dir: &Path, path: &Path, data: SessionData, ttl: Duration) -> Result<(), String> {
+    let mut stored = Map::new();
+    stored.insert("expires".to_string(), Value::from(unix_now() + ttl.as_secs()));
+    stored.insert("data".to_string(), Value::Object(data.into_iter().collect()));
+    let buf = serde_json::to_vec(&stored).map_err(|e| e.to_string())?;
+
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::File::create(path))
+        .and_then(|mut f| f.write_all(&buf))
+        .map_err(|e| e.to_string())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use hyper::{Method, Request as HyperRequest};
+    use hyper::header::SetCookie;
+    use CookieKeys;
+
+    /// Memory store that logs what the response hook asks of it.
+    #[derive(Clone)]
+    struct Recorder {
+        memory: Rc<MemoryStore>,
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl SessionStore for Recorder {
+        fn load(&self, id: &str) -> Box<Future<Item = Option<SessionData>, Error = Error>> {
+            self.memory.load(id)
+        }
+
+        fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Box<Future<Item = (), Error = Error>> {
+            self.log.borrow_mut().push(format!("save {}", id));
+            self.memory.save(id, data, ttl)
+        }
+
+        fn destroy(&self, id: &str) -> Box<Future<Item = (), Error = Error>> {
+            self.log.borrow_mut().push(format!("destroy {}", id));
+            self.memory.destroy(id)
+        }
+    }
+
+    fn request(state: &Container, cookie: Option<&str>) -> (Request, Session) {
+        let mut r = HyperRequest::new(Method::Get, "/".parse().unwrap());
+        if let Some(cookie) = cookie {
+            r.headers_mut().set_raw("Cookie", cookie.to_string());
+        }
+        let req = Request::new(r);
+        let session = Session::for_request(&req, state).wait().unwrap();
+        (req, session)
+    }
+
+    /// Runs the response hooks, returns the session cookie that was set.
+    fn finish(req: Request) -> Option<String> {
+        let resp = req.extensions().run_hooks(Response::new()).wait().unwrap();
+        resp.headers().get::<SetCookie>().map(|c| {
+            c.0[0].split(';').next().unwrap().to_string()
+        })
+    }
+
+    #[test]
+    fn memory_store_expiry() {
+        let store = MemoryStore::new();
+        let mut data = SessionData::new();
+        data.insert("user".to_string(), Value::from(42));
+
+        store.save("alive", &data, Duration::from_secs(60)).wait().unwrap();
+        store.save("dead", &data, Duration::from_secs(0)).wait().unwrap();
+
+        assert_eq!(store.load("alive").wait().unwrap(), Some(data));
+        assert_eq!(store.load("dead").wait().unwrap(), None);
+    }
+
+    #[test]
+    fn file_store() {
+        let dir = env::temp_dir().join(format!("unrest-sessions-{}", new_session_id()));
+        let store = FileStore::new(&dir);
+        let mut data = SessionData::new();
+        data.insert("user".to_string(), Value::from(42));
+
+        assert_eq!(store.load("missing").wait().unwrap(), None);
+        store.save("alive", &data, Duration::from_secs(60)).wait().unwrap();
+        store.save("dead", &data, Duration::from_secs(0)).wait().unwrap();
+
+        assert_eq!(store.load("alive").wait().unwrap(), Some(data));
+        assert_eq!(store.load("dead").wait().unwrap(), None);
+        assert!(!dir.join("dead.json").exists());
+
+        store.destroy("alive").wait().unwrap();
+        assert_eq!(store.load("alive").wait().unwrap(), None);
+        match store.load("../etc/passwd").wait() {
+            Err(Error::OtherUsersFault(_)) => {}
+            r => panic!("expected an invalid id error, got {:?}", r),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn persist_and_regenerate() {
+        let recorder = Recorder {
+            memory: Rc::new(MemoryStore::new()),
+            log: Rc::new(RefCell::new(Vec::new())),
+        };
+        let mut state = Container::new();
+        state.set(CookieKeys::generate());
+        state.set(Sessions::new(recorder.clone()));
+
+        let (req, session) = request(&state, None);
+        session.set("user", 42).unwrap();
+        let cookie = finish(req).expect("session cookie");
+        let id = session.id().unwrap();
+        assert_eq!(*recorder.log.borrow(), vec![format!("save {}", id)]);
+
+        let (req, session) = request(&state, Some(&cookie));
+        assert_eq!(session.get::<u32>("user"), Some(42));
+        assert_eq!(finish(req), None);
+        assert_eq!(recorder.log.borrow().len(), 1);
+
+        let (req, session) = request(&state, Some(&cookie));
+        session.regenerate();
+        let new_cookie = finish(req).expect("new session cookie");
+        let new_id = session.id().unwrap();
+        assert!(new_id != id && new_cookie != cookie);
+        let mut log = recorder.log.borrow()[1..].to_vec();
+        log.sort();
+        assert_eq!(log, vec![format!("destroy {}", id), format!("save {}", new_id)]);
+        assert_eq!(recorder.memory.load(&id).wait().unwrap(), None);
+
+        let (req, session) = request(&state, Some(&new_cookie));
+        assert_eq!(session.get::<u32>("user"), Some(42));
+        finish(req);
+    }
+}
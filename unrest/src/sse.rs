@@ -0,0 +1,275 @@
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::{future, Future, Stream, Poll, Async};
+use hyper::header::{CacheControl, CacheDirective, ContentType};
+use serde::Serialize;
+use serde_json;
+use tokio_core::reactor::{Handle, Timeout};
+
+use {Request, Response, Responder, Params, Container, Error};
+use from_request::FromRequest;
+use streamed::spawn_body;
+
+/// After how long without events a stream sends a comment, so proxies keep it open.
+pub const SSE_KEEP_ALIVE: u64 = 15;
+
+/// One server-sent event.
+///
+/// Line breaks in `id` and `event` are dropped, multi-line `data` is sent
+/// as several `data:` lines and arrives joined with `\n`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Event {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+    pub retry: Option<Duration>,
+}
+
+/// `text/event-stream` response fed by a stream of events.
+///
+/// Take `handle: Handle` as a handler argument to build one, and
+/// `Option<LastEventId>` to resume after a reconnect.
+pub struct EventStream<S> {
+    events: S,
+    handle: Handle,
+    keep_alive: Option<Duration>,
+}
+
+/// The `Last-Event-ID` a reconnecting client sends, 400 if it's missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastEventId(pub String);
+
+struct Frames<S> {
+    events: S,
+    keep_alive: Option<(Timeout, Duration)>,
+}
+
+impl Event {
+    pub fn data<D: Into<String>>(data: D) -> Event {
+        Event {
+            data: data.into(),
+            ..Event::default()
+        }
+    }
+
+    /// Event with `value` serialized as JSON data.
+    pub fn json<T: Serialize>(value: &T) -> Result<Event, Error> {
+        Ok(Event::data(serde_json::to_string(value)?))
+    }
+
+    pub fn id<I: Into<String>>(mut self, id: I) -> Event {
+        self.id = Some(id.into());
+        self
+    }
+
+    pub fn event<E: Into<String>>(mut self, event: E) -> Event {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Reconnection delay the client should use from now on.
+    pub fn retry(mut self, retry: Duration) -> Event {
+        self.retry = Some(retry);
+        self
+    }
+
+    fn frame(&self) -> Bytes {
+        let mut out = String::new();
+        if let Some(ref event) = self.event {
+            field(&mut out, "event", event);
+        }
+        if let Some(ref id) = self.id {
+            field(&mut out, "id", &id.replace('\0', ""));
+        }
+        if let Some(retry) = self.retry {
+            let ms = retry.as_secs() * 1000 + u64::from(retry.subsec_nanos() / 1_000_000);
+            out.push_str(&format!("retry: {}\n", ms));
+        }
+
+        let data = self.data.replace("\r\n", "\n").replace('\r', "\n");
+        for line in data.split('\n') {
+            out.push_str("data: ");
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        out.push('\n');
+        out.into()
+    }
+}
+
+impl<S> EventStream<S>
+where
+    S: Stream<Item = Event> + 'static,
+    S::Error: Into<Error>,
+{
+    pub fn new(events: S, handle: &Handle) -> EventStream<S> {
+        EventStream {
+            events,
+            handle: handle.clone(),
+            keep_alive: Some(Duration::from_secs(SSE_KEEP_ALIVE)),
+        }
+    }
+
+    /// Sends a comment once the stream was idle for `every`.
+    pub fn keep_alive(mut self, every: Duration) -> EventStream<S> {
+        self.keep_alive = Some(every);
+        self
+    }
+
+    /// Without keep-alives a client going away is only noticed when the
+    /// next event can't be sent, until then the stream is kept alive.
+    pub fn no_keep_alive(mut self) -> EventStream<S> {
+        self.keep_alive = None;
+        self
+    }
+}
+
+impl<S> Responder for EventStream<S>
+where
+    S: Stream<Item = Event> + 'static,
+    S::Error: Into<Error>,
+{
+    fn respond(self) -> Response {
+        let keep_alive = self.keep_alive.and_then(|every| {
+            Timeout::new(every, &self.handle).ok().map(|t| (t, every))
+        });
+        let frames = Frames {
+            events: self.events,
+            keep_alive,
+        };
+
+        Response::new()
+            .with_header(ContentType("text/event-stream".parse().expect("valid sse mime")))
+            .with_header(CacheControl(vec![CacheDirective::NoCache]))
+            .with_body(spawn_body(frames, &self.handle))
+    }
+}
+
+impl<S> Frames<S> {
+    /// Pushes the next keep-alive back to a full period from now.
+    fn reset_keep_alive(&mut self) {
+        if let Some((ref mut timeout, every)) = self.keep_alive {
+            timeout.reset(Instant::now() + every);
+        }
+    }
+}
+
+/// Ends with the events, keep-alives only fill the gaps between them.
+impl<S> Stream for Frames<S>
+where
+    S: Stream<Item = Event>,
+    S::Error: Into<Error>,
+{
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        match self.events.poll().map_err(Into::into)? {
+            Async::Ready(Some(event)) => {
+                self.reset_keep_alive();
+                return Ok(Async::Ready(Some(event.frame())));
+            }
+            Async::Ready(None) => return Ok(Async::Ready(None)),
+            Async::NotReady => {}
+        }
+
+        let idle = match self.keep_alive {
+            Some((ref mut timeout, _)) => timeout.poll()?.is_ready(),
+            None => false,
+        };
+        if idle {
+            self.reset_keep_alive();
+            return Ok(Async::Ready(Some(Bytes::from_static(b":\n\n"))));
+        }
+        Ok(Async::NotReady)
+    }
+}
+
+impl LastEventId {
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for LastEventId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromRequest for LastEventId {
+    fn from_request(req: &Request, _: &Params, _: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        let res = match req.headers().get_raw("Last-Event-ID").and_then(|raw| raw.one()) {
+            Some(raw) => {
+                String::from_utf8(raw.to_vec()).map(LastEventId).map_err(|_| {
+                    Error::HeaderParseError("Last-Event-ID")
+                })
+            }
+            None => Err(Error::HeaderNotFound("Last-Event-ID")),
+        };
+
+        Box::new(future::result(res))
+    }
+}
+
+fn field(out: &mut String, name: &str, value: &str) {
+    out.push_str(name);
+    out.push_str(": ");
+    out.extend(value.chars().filter(|c| *c != '\n' && *c != '\r'));
+    out.push('\n');
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use tokio_core::reactor::{Core, Interval};
+
+    #[test]
+    fn framing() {
+        let event = Event::data("one\ntwo\r\nthree")
+            .event("update")
+            .id("4\n2")
+            .retry(Duration::from_millis(1500));
+        assert_eq!(
+            &event.frame()[..],
+            &b"event: update\nid: 42\nretry: 1500\ndata: one\ndata: two\ndata: three\n\n"[..]
+        );
+
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let events = stream::iter_ok::<_, Error>(vec![Event::data("a"), Event::data("")]);
+        let resp = EventStream::new(events, &handle).respond();
+        let body = core.run(resp.body().concat2()).unwrap();
+        assert_eq!(&body[..], &b"data: a\n\ndata: \n\n"[..]);
+    }
+
+    #[test]
+    fn keep_alive_only_when_idle() {
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let every = Duration::from_millis(100);
+
+        let busy = Interval::new(Duration::from_millis(30), &handle)
+            .unwrap()
+            .take(6)
+            .map(|_| Event::data("x"));
+        let resp = EventStream::new(busy, &handle).keep_alive(every).respond();
+        let body = core.run(resp.body().concat2()).unwrap();
+        assert_eq!(&body[..], &b"data: x\n\n"[..].repeat(6)[..]);
+
+        let idle = Timeout::new(Duration::from_millis(250), &handle)
+            .unwrap()
+            .into_stream()
+            .map(|_| Event::data("late"));
+        let resp = EventStream::new(idle, &handle).keep_alive(every).respond();
+        let body = core.run(resp.body().concat2()).unwrap();
+        assert_eq!(&body[..], &b":\n\n:\n\ndata: late\n\n"[..]);
+    }
+}
@@ -0,0 +1,730 @@
+use std::cmp;
+use std::collections::VecDeque;
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::{future, Future, Sink, Stream, Poll, Async};
+use hyper::{self, Body, Chunk, Headers, StatusCode};
+use hyper::header::{AcceptEncoding, AcceptRanges, ByteRangeSpec, ContentEncoding, ContentLength,
+                    ContentRange, ContentRangeSpec, ContentType, ETag, Encoding, EntityTag,
+                    HttpDate, IfModifiedSince, IfNoneMatch, IfRange, LastModified, Range,
+                    RangeUnit, q};
+use hyper::mime::Mime;
+use url::percent_encoding::percent_decode;
+use rand;
+
+use {Request, Response, Responder, Params, Container, Error, Handler, Redirect};
+use request::Method;
+use body::file_pool;
+
+/// Size of the chunks files are streamed in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Requests with more ranges than this get the whole file.
+const MAX_RANGES: usize = 16;
+
+/// A file on disk as a response.
+///
+/// Responding with it directly sends the whole file, use `respond_to` with
+/// the request headers to answer conditional and range requests as well.
+#[derive(Debug)]
+pub struct NamedFile {
+    path: PathBuf,
+    file: File,
+    meta: Metadata,
+    content_type: Mime,
+    encoding: Option<Encoding>,
+}
+
+/// Serves files under a directory, mount it with `Router::mount`.
+///
+/// Paths are percent-decoded, anything that would escape the root, symlinks
+/// pointing outside of it included, and hidden files are answered with 404.
+/// Lookups and opening files run on the file pool.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    index: Option<String>,
+    precompressed: bool,
+}
+
+enum Segment {
+    Bytes(Vec<u8>),
+    File { start: u64, len: u64 },
+}
+
+/// What a request path resolved to, found on the file pool.
+enum Lookup {
+    File(NamedFile),
+    /// A directory asked for without the trailing slash.
+    Directory,
+    Missing,
+}
+
+struct FileChunks {
+    file: File,
+    segments: VecDeque<Segment>,
+}
+
+impl NamedFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<NamedFile, Error> {
+        open_file(path.as_ref())?.ok_or(Error::NotFound)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.meta
+    }
+
+    pub fn content_type(&self) -> &Mime {
+        &self.content_type
+    }
+
+    /// Overrides the type guessed from the file extension.
+    pub fn with_content_type(mut self, content_type: Mime) -> NamedFile {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Marks the file as stored with `encoding`, e.g. a gzipped copy.
+    pub fn with_encoding(mut self, encoding: Encoding) -> NamedFile {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Weak tag built from size and modification time.
+    pub fn etag(&self) -> EntityTag {
+        EntityTag::weak(format!("{:x}-{:x}", self.meta.len(), self.modified()))
+    }
+
+    pub fn last_modified(&self) -> HttpDate {
+        HttpDate::from(UNIX_EPOCH + ::std::time::Duration::from_secs(self.modified()))
+    }
+
+    /// Responds with 304 if the client's copy is fresh, with 206 or 416
+    /// to a `Range` and with the whole file otherwise.
+    pub fn respond_to(self, headers: &Headers) -> Response {
+        if self.is_fresh(headers) {
+            return self.headers(Response::new().with_status(StatusCode::NotModified));
+        }
+
+        let len = self.meta.len();
+        let ranges = match headers.get::<Range>() {
+            Some(&Range::Bytes(ref specs)) if specs.len() <= MAX_RANGES && self.if_range(headers) => {
+                Some(satisfiable(specs, len))
+            }
+            _ => None,
+        };
+
+        match ranges {
+            None => self.respond(),
+            Some(ref ranges) if ranges.is_empty() => {
+                Response::new()
+                    .with_status(StatusCode::RangeNotSatisfiable)
+                    .with_header(ContentRange(ContentRangeSpec::Bytes {
+                        range: None,
+                        instance_length: Some(len),
+                    }))
+            }
+            Some(ranges) => {
+                if ranges.len() == 1 {
+                    self.single_range(ranges[0])
+                } else {
+                    self.byteranges(&ranges)
+                }
+            }
+        }
+    }
+
+    fn modified(&self) -> u64 {
+        self.meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// `If-None-Match` wins over `If-Modified-Since` when both are sent.
+    fn is_fresh(&self, headers: &Headers) -> bool {
+        match headers.get::<IfNoneMatch>() {
+            Some(&IfNoneMatch::Any) => return true,
+            Some(&IfNoneMatch::Items(ref tags)) => {
+                let etag = self.etag();
+                return tags.iter().any(|t| t.weak_eq(&etag));
+            }
+            None => {}
+        }
+
+        match headers.get::<IfModifiedSince>() {
+            Some(&IfModifiedSince(since)) => self.modified() <= secs(since),
+            None => false,
+        }
+    }
+
+    /// Ranges only apply if the client's partial copy is still current.
+    ///
+    /// Our tags are weak, so only a date can validate a range.
+    fn if_range(&self, headers: &Headers) -> bool {
+        match headers.get::<IfRange>() {
+            Some(&IfRange::EntityTag(_)) => false,
+            Some(&IfRange::Date(date)) => self.modified() == secs(date),
+            None => true,
+        }
+    }
+
+    fn headers(&self, resp: Response) -> Response {
+        let resp = resp.with_header(ETag(self.etag()))
+            .with_header(LastModified(self.last_modified()))
+            .with_header(AcceptRanges(vec![RangeUnit::Bytes]));
+
+        match self.encoding {
+            Some(ref encoding) => resp.with_header(ContentEncoding(vec![encoding.clone()])),
+            None => resp,
+        }
+    }
+
+    fn single_range(self, (start, end): (u64, u64)) -> Response {
+        let len = self.meta.len();
+        let resp = self.headers(Response::new())
+            .with_status(StatusCode::PartialContent)
+            .with_header(ContentType(self.content_type.clone()))
+            .with_header(ContentRange(ContentRangeSpec::Bytes {
+                range: Some((start, end)),
+                instance_length: Some(len),
+            }))
+            .with_header(ContentLength(end - start + 1));
+
+        let segment = Segment::File {
+            start,
+            len: end - start + 1,
+        };
+        resp.with_body(stream_file(self.file, vec![segment]))
+    }
+
+    /// Several ranges go out as `multipart/byteranges`.
+    fn byteranges(self, ranges: &[(u64, u64)]) -> Response {
+        let len = self.meta.len();
+        let boundary = format!("{:016x}", rand::random::<u64>());
+
+        let mut segments = Vec::new();
+        let mut total = 0;
+        for &(start, end) in ranges {
+            let head = format!(
+                "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                boundary,
+                self.content_type,
+                start,
+                end,
+                len
+            );
+            total += head.len() as u64 + end - start + 1;
+            segments.push(Segment::Bytes(head.into_bytes()));
+            segments.push(Segment::File {
+                start,
+                len: end - start + 1,
+            });
+        }
+        let tail = format!("\r\n--{}--\r\n", boundary);
+        total += tail.len() as u64;
+        segments.push(Segment::Bytes(tail.into_bytes()));
+
+        let content_type = format!("multipart/byteranges; boundary={}", boundary)
+            .parse::<Mime>()
+            .expect("valid multipart mime");
+
+        self.headers(Response::new())
+            .with_status(StatusCode::PartialContent)
+            .with_header(ContentType(content_type))
+            .with_header(ContentLength(total))
+            .with_body(stream_file(self.file, segments))
+    }
+}
+
+impl Responder for NamedFile {
+    fn respond(self) -> Response {
+        let len = self.meta.len();
+        self.headers(Response::new())
+            .with_header(ContentType(self.content_type.clone()))
+            .with_header(ContentLength(len))
+            .with_body(stream_file(self.file, vec![Segment::File { start: 0, len }]))
+    }
+}
+
+impl StaticFiles {
+    /// Serves `root`, directories are answered with their `index.html`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> StaticFiles {
+        StaticFiles {
+            root: root.into(),
+            index: Some("index.html".to_string()),
+            precompressed: false,
+        }
+    }
+
+    /// File served for directory requests.
+    pub fn index<S: Into<String>>(mut self, name: S) -> StaticFiles {
+        self.index = Some(name.into());
+        self
+    }
+
+    /// Answers directory requests with 404.
+    pub fn no_index(mut self) -> StaticFiles {
+        self.index = None;
+        self
+    }
+
+    /// Serves `file.br` or `file.gz` instead of `file` when they exist and
+    /// the client accepts the encoding.
+    pub fn precompressed(mut self, enabled: bool) -> StaticFiles {
+        self.precompressed = enabled;
+        self
+    }
+
+    /// Blocks, runs on the file pool.
+    fn lookup(&self, rel: &Path, trailing_slash: bool, encodings: &[(Encoding, &str)]) -> io::Result<Lookup> {
+        let root = match self.root.canonicalize() {
+            Ok(root) => root,
+            Err(e) => return missing::<()>(e).map(|_| Lookup::Missing),
+        };
+
+        let mut path = self.root.join(rel);
+        if path.is_dir() {
+            let index = match self.index {
+                Some(ref index) => index,
+                None => return Ok(Lookup::Missing),
+            };
+            if !trailing_slash {
+                return Ok(Lookup::Directory);
+            }
+            path.push(index);
+        }
+
+        for &(ref encoding, ext) in encodings {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(".");
+            name.push(ext);
+            if let Ok(Some(file)) = open_under(&root, Path::new(&name)) {
+                return Ok(Lookup::File(
+                    file.with_content_type(guess_mime(&path)).with_encoding(encoding.clone()),
+                ));
+            }
+        }
+
+        Ok(open_under(&root, &path)?.map_or(Lookup::Missing, Lookup::File))
+    }
+
+    /// Precompressed copies worth looking for, in order of preference.
+    fn encodings(&self, headers: &Headers) -> Vec<(Encoding, &'static str)> {
+        let accepted = match headers.get::<AcceptEncoding>() {
+            Some(accepted) if self.precompressed => accepted,
+            _ => return Vec::new(),
+        };
+
+        vec![(Encoding::Brotli, "br"), (Encoding::Gzip, "gz")]
+            .into_iter()
+            .filter(|&(ref encoding, _)| {
+                accepted.iter().any(|a| a.item == *encoding && a.quality > q(0))
+            })
+            .collect()
+    }
+}
+
+impl Handler for StaticFiles {
+    fn handle(&self, req: Request, params: Params, _: Container) -> Box<Future<Item = Response, Error = Error>> {
+        let rel = match sanitize(params.find("path").unwrap_or("")) {
+            Some(rel) => rel,
+            None => return Box::new(future::err(Error::NotFound)),
+        };
+        let trailing_slash = req.path().ends_with('/');
+        let encodings = self.encodings(req.headers());
+        let precompressed = self.precompressed;
+
+        let files = self.clone();
+        let lookup = file_pool().spawn_fn(move || files.lookup(&rel, trailing_slash, &encodings));
+
+        Box::new(lookup.map_err(Error::from).and_then(move |lookup| match lookup {
+            Lookup::File(file) => {
+                let mut resp = file.respond_to(req.headers());
+                if precompressed {
+                    resp.headers_mut().set_raw("Vary", "Accept-Encoding");
+                }
+                Ok(resp)
+            }
+            Lookup::Directory => Ok(Redirect::moved(format!("{}/", req.path())).respond()),
+            Lookup::Missing => Err(Error::NotFound),
+        }))
+    }
+
+    /// Matches the mount prefix itself, with a trailing slash and everything below.
+    fn path(&self) -> &'static str {
+        "(/(*path))"
+    }
+
+    fn method(&self) -> Method {
+        Method::Get
+    }
+}
+
+impl FileChunks {
+    fn read(&mut self, start: u64, len: u64) -> io::Result<Chunk> {
+        let n = cmp::min(len, CHUNK_SIZE as u64) as usize;
+        let mut buf = vec![0; n];
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.read_exact(&mut buf)?;
+
+        if len > n as u64 {
+            self.segments.push_front(Segment::File {
+                start: start + n as u64,
+                len: len - n as u64,
+            });
+        }
+        Ok(buf.into())
+    }
+}
+
+/// Runs on the file pool, so reads may block.
+impl Stream for FileChunks {
+    type Item = Chunk;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Chunk>, io::Error> {
+        let chunk = match self.segments.pop_front() {
+            None => return Ok(Async::Ready(None)),
+            Some(Segment::Bytes(bytes)) => bytes.into(),
+            Some(Segment::File { start, len }) => {
+                match self.read(start, len) {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        self.segments.clear();
+                        return Err(e);
+                    }
+                }
+            }
+        };
+        Ok(Async::Ready(Some(chunk)))
+    }
+}
+
+/// Body fed from the file pool, a chunk is read only once the previous one
+/// was taken by the connection.
+fn stream_file(file: File, segments: Vec<Segment>) -> Body {
+    let (tx, body) = Body::pair();
+    let chunks = FileChunks {
+        file,
+        segments: segments.into(),
+    };
+
+    let chunks = chunks.then(|res| Ok::<_, ()>(res.map_err(hyper::Error::from)));
+    let send = tx.sink_map_err(|_| ()).send_all(chunks).map(|_| ());
+    file_pool().spawn(send).forget();
+
+    body
+}
+
+/// Satisfiable ranges in file order, overlapping and adjacent ones merged
+/// so no byte is sent twice (RFC 7233, 6.1).
+fn satisfiable(specs: &[ByteRangeSpec], len: u64) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = specs
+        .iter()
+        .filter_map(|spec| spec.to_satisfiable_range(len))
+        .collect();
+    ranges.sort();
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = cmp::max(last.1, end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn secs(date: HttpDate) -> u64 {
+    SystemTime::from(date)
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn open_file(path: &Path) -> io::Result<Option<NamedFile>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return missing(e),
+    };
+    let meta = file.metadata()?;
+    if !meta.is_file() {
+        return Ok(None);
+    }
+
+    Ok(Some(NamedFile {
+        path: path.to_path_buf(),
+        file,
+        meta,
+        content_type: guess_mime(path),
+        encoding: None,
+    }))
+}
+
+/// Opens `path` only if it resolves, following symlinks, to a file under `root`.
+fn open_under(root: &Path, path: &Path) -> io::Result<Option<NamedFile>> {
+    let real = match path.canonicalize() {
+        Ok(real) => real,
+        Err(e) => return missing(e),
+    };
+    if !real.starts_with(root) {
+        return Ok(None);
+    }
+
+    Ok(open_file(&real)?.map(|file| file.with_content_type(guess_mime(path))))
+}
+
+/// Files that don't exist or can't be read are a 404, not a server error.
+fn missing<T>(e: io::Error) -> io::Result<Option<T>> {
+    match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied => Ok(None),
+        _ => Err(e),
+    }
+}
+
+/// Turns a request path into a relative path that stays under the root.
+fn sanitize(raw: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(raw.as_bytes()).decode_utf8().ok()?;
+    if decoded.contains('\0') || decoded.contains('\\') {
+        return None;
+    }
+
+    let mut path = PathBuf::new();
+    for seg in decoded.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        if seg.starts_with('.') {
+            return None;
+        }
+        path.push(seg);
+    }
+
+    if path.components().all(|c| match c {
+        Component::Normal(_) => true,
+        _ => false,
+    })
+    {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn guess_mime(path: &Path) -> Mime {
+    let ext = path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    let mime = match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "text/xml; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    };
+    mime.parse().expect("valid mime")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use hyper::Request as HyperRequest;
+    use Router;
+
+    #[test]
+    fn sanitize_paths() {
+        assert_eq!(sanitize("css/app.css"), Some(PathBuf::from("css/app.css")));
+        assert_eq!(sanitize("/a//./b%20c"), Some(PathBuf::from("a/b c")));
+        assert_eq!(sanitize("../etc/passwd"), None);
+        assert_eq!(sanitize("a/%2e%2e/%2e%2e/secret"), None);
+        assert_eq!(sanitize(".git/config"), None);
+        assert_eq!(sanitize("a%00.txt"), None);
+    }
+
+    #[test]
+    fn ranges_and_conditionals() {
+        let path = env::temp_dir().join(format!("unrest-static-{}.txt", rand::random::<u32>()));
+        fs::File::create(&path).unwrap().write_all(b"0123456789").unwrap();
+        let body = |resp: Response| resp.body().concat2().wait().unwrap().to_vec();
+
+        let mut headers = Headers::new();
+        headers.set(Range::bytes(2, 4));
+        let resp = NamedFile::open(&path).unwrap().respond_to(&headers);
+        assert_eq!(resp.status(), StatusCode::PartialContent);
+        assert_eq!(body(resp), b"234");
+
+        let mut headers = Headers::new();
+        headers.set(Range::bytes_multi(vec![(0, 1), (8, 9)]));
+        let resp = NamedFile::open(&path).unwrap().respond_to(&headers);
+        let len = **resp.headers().get::<ContentLength>().unwrap();
+        let text = String::from_utf8(body(resp)).unwrap();
+        assert_eq!(text.len() as u64, len);
+        assert!(text.contains("Content-Range: bytes 8-9/10\r\n\r\n89\r\n"));
+
+        let mut headers = Headers::new();
+        headers.set_raw("Range", "bytes=0-,0-,0-,5-");
+        let resp = NamedFile::open(&path).unwrap().respond_to(&headers);
+        assert_eq!(resp.status(), StatusCode::PartialContent);
+        assert_eq!(body(resp), b"0123456789");
+
+        let mut headers = Headers::new();
+        headers.set(Range::bytes_multi(vec![(5, 7), (0, 2), (1, 3), (4, 4)]));
+        let resp = NamedFile::open(&path).unwrap().respond_to(&headers);
+        assert_eq!(resp.headers().get::<ContentRange>(), Some(&ContentRange(ContentRangeSpec::Bytes {
+            range: Some((0, 7)),
+            instance_length: Some(10),
+        })));
+        assert_eq!(body(resp), b"01234567");
+
+        let mut headers = Headers::new();
+        headers.set(Range::bytes(20, 30));
+        let resp = NamedFile::open(&path).unwrap().respond_to(&headers);
+        assert_eq!(resp.status(), StatusCode::RangeNotSatisfiable);
+
+        let etag = NamedFile::open(&path).unwrap().etag();
+        let mut headers = Headers::new();
+        headers.set(IfNoneMatch::Items(vec![etag]));
+        let resp = NamedFile::open(&path).unwrap().respond_to(&headers);
+        assert_eq!(resp.status(), StatusCode::NotModified);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn mounted_handler() {
+        let dir = env::temp_dir().join(format!("unrest-static-{}", rand::random::<u32>()));
+        let root = dir.join("public");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        let write = |path: PathBuf, contents: &[u8]| fs::File::create(path).unwrap().write_all(contents).unwrap();
+        write(root.join("docs/index.html"), b"<h1>docs</h1>");
+        write(dir.join("secret.txt"), b"secret");
+        write(root.join("app.js"), b"plain");
+        write(root.join("app.js.gz"), b"gzipped");
+        write(root.join("app.js.br"), b"brotli");
+
+        let mut router = Router::new();
+        router.mount("/static", Box::new(StaticFiles::new(&root).precompressed(true)));
+        let get = |uri: &str, accept_encoding: Option<&'static str>| {
+            let mut req = HyperRequest::new(Method::Get, uri.parse().unwrap());
+            if let Some(value) = accept_encoding {
+                req.headers_mut().set_raw("Accept-Encoding", value);
+            }
+            router
+                .run(Request::new(req), Container::new())
+                .and_then(|f| f.wait())
+        };
+        let body = |resp: Response| resp.body().concat2().wait().unwrap().to_vec();
+
+        let resp = get("/static/docs", None).unwrap();
+        assert_eq!(resp.status(), StatusCode::MovedPermanently);
+        assert_eq!(resp.headers().get_raw("Location").unwrap().one(), Some(&b"/static/docs/"[..]));
+
+        let resp = get("/static/docs/", None).unwrap();
+        assert_eq!(resp.status(), StatusCode::Ok);
+        assert_eq!(body(resp), b"<h1>docs</h1>");
+
+        let escapes = [
+            "/static/../secret.txt",
+            "/static/%2e%2e/secret.txt",
+            "/static/docs/%2e%2e/%2e%2e/secret.txt",
+        ];
+        for uri in &escapes {
+            match get(uri, None) {
+                Err(Error::NotFound) | Err(Error::RouterError) => {}
+                Err(e) => panic!("{}: unexpected {:?}", uri, e),
+                Ok(resp) => panic!("{}: served {:?}", uri, body(resp)),
+            }
+        }
+
+        let resp = get("/static/app.js", Some("gzip, br")).unwrap();
+        let encoding = |resp: &Response| resp.headers().get::<ContentEncoding>().map(|e| e.0.clone());
+        assert_eq!(encoding(&resp), Some(vec![Encoding::Brotli]));
+        assert_eq!(resp.headers().get_raw("Vary").unwrap().one(), Some(&b"Accept-Encoding"[..]));
+        assert_eq!(resp.headers().get::<ContentType>().unwrap().0, guess_mime(Path::new("app.js")));
+        assert_eq!(body(resp), b"brotli");
+
+        let resp = get("/static/app.js", Some("gzip, br;q=0")).unwrap();
+        assert_eq!(encoding(&resp), Some(vec![Encoding::Gzip]));
+        assert_eq!(body(resp), b"gzipped");
+
+        let resp = get("/static/app.js", None).unwrap();
+        assert_eq!(encoding(&resp), None);
+        assert_eq!(resp.headers().get_raw("Vary").unwrap().one(), Some(&b"Accept-Encoding"[..]));
+        assert_eq!(body(resp), b"plain");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_stay_under_root() {
+        use std::os::unix::fs::symlink;
+
+        let dir = env::temp_dir().join(format!("unrest-static-{}", rand::random::<u32>()));
+        let root = dir.join("public");
+        fs::create_dir_all(&root).unwrap();
+        fs::File::create(dir.join("secret.txt")).unwrap().write_all(b"secret").unwrap();
+        fs::File::create(root.join("app.js")).unwrap().write_all(b"app").unwrap();
+        symlink(dir.join("secret.txt"), root.join("leak.txt")).unwrap();
+        symlink(&dir, root.join("up")).unwrap();
+        symlink(root.join("app.js"), root.join("alias.js")).unwrap();
+
+        let mut router = Router::new();
+        router.mount("/static", Box::new(StaticFiles::new(&root)));
+        let get = |uri: &str| {
+            let req = HyperRequest::new(Method::Get, uri.parse().unwrap());
+            router.run(Request::new(req), Container::new()).and_then(|f| f.wait())
+        };
+
+        for uri in &["/static/leak.txt", "/static/up/secret.txt"] {
+            match get(uri) {
+                Err(Error::NotFound) => {}
+                Err(e) => panic!("{}: unexpected {:?}", uri, e),
+                Ok(resp) => panic!("{}: served {:?}", uri, resp.body().concat2().wait().unwrap()),
+            }
+        }
+
+        let resp = get("/static/alias.js").unwrap();
+        assert_eq!(resp.headers().get::<ContentType>().unwrap().0, guess_mime(Path::new("alias.js")));
+        assert_eq!(resp.body().concat2().wait().unwrap().to_vec(), b"app");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+use hyper::StatusCode;
+use hyper::header::{Headers, Location};
+
+use {Responder, Response, Error};
+
+/// 201 Created with a `Location` of the new resource and an optional body.
+#[derive(Debug)]
+pub struct Created<T = ()> {
+    location: String,
+    body: T,
+}
+
+/// 202 Accepted, the request was queued for processing.
+#[derive(Debug)]
+pub struct Accepted<T = ()>(pub T);
+
+/// 204 No Content.
+#[derive(Debug)]
+pub struct NoContent;
+
+/// Any status with the body of `T`.
+#[derive(Debug)]
+pub struct Custom<T>(pub StatusCode, pub T);
+
+impl Created<()> {
+    pub fn new<U: Into<String>>(location: U) -> Created<()> {
+        Created {
+            location: location.into(),
+            body: (),
+        }
+    }
+}
+
+impl<T> Created<T> {
+    pub fn with_body<B>(self, body: B) -> Created<B> {
+        Created {
+            location: self.location,
+            body,
+        }
+    }
+}
+
+impl<T: Responder> Responder for Created<T> {
+    fn respond(self) -> Response {
+        self.body
+            .respond()
+            .with_status(StatusCode::Created)
+            .with_header(Location::new(self.location))
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        Ok(self.body
+            .into_response()?
+            .with_status(StatusCode::Created)
+            .with_header(Location::new(self.location)))
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        Ok(self.body
+            .negotiate(headers)?
+            .with_status(StatusCode::Created)
+            .with_header(Location::new(self.location)))
+    }
+}
+
+impl<T: Responder> Responder for Accepted<T> {
+    fn respond(self) -> Response {
+        self.0.respond().with_status(StatusCode::Accepted)
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        Ok(self.0.into_response()?.with_status(StatusCode::Accepted))
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        Ok(self.0.negotiate(headers)?.with_status(StatusCode::Accepted))
+    }
+}
+
+impl Responder for NoContent {
+    fn respond(self) -> Response {
+        Response::new().with_status(StatusCode::NoContent)
+    }
+}
+
+impl<T: Responder> Responder for Custom<T> {
+    fn respond(self) -> Response {
+        self.1.respond().with_status(self.0)
+    }
+
+    fn into_response(self) -> Result<Response, Error> {
+        Ok(self.1.into_response()?.with_status(self.0))
+    }
+
+    fn negotiate(self, headers: &Headers) -> Result<Response, Error> {
+        Ok(self.1.negotiate(headers)?.with_status(self.0))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::ContentType;
+
+    fn location(resp: &Response) -> Option<&str> {
+        resp.headers().get::<Location>().map(|l| &**l)
+    }
+
+    #[test]
+    fn status_wrappers() {
+        let resp = Created::new("/users/1").respond();
+        assert_eq!(resp.status(), StatusCode::Created);
+        assert_eq!(location(&resp), Some("/users/1"));
+
+        let resp = Created::new("/users/1").with_body("made").respond();
+        assert_eq!(resp.status(), StatusCode::Created);
+        assert_eq!(location(&resp), Some("/users/1"));
+        assert_eq!(resp.headers().get::<ContentType>(), Some(&ContentType::plaintext()));
+
+        assert_eq!(Accepted("queued").respond().status(), StatusCode::Accepted);
+        assert_eq!(NoContent.respond().status(), StatusCode::NoContent);
+        assert_eq!(Custom(StatusCode::ImATeapot, ()).respond().status(), StatusCode::ImATeapot);
+    }
+
+    #[test]
+    fn tuples() {
+        let resp = (StatusCode::Conflict, "taken").respond();
+        assert_eq!(resp.status(), StatusCode::Conflict);
+        assert_eq!(resp.headers().get::<ContentType>(), Some(&ContentType::plaintext()));
+
+        let mut headers = Headers::new();
+        headers.set(ContentType::json());
+        headers.set(Location::new("/elsewhere"));
+
+        let resp = (StatusCode::Created, headers.clone(), "{}").respond();
+        assert_eq!(resp.status(), StatusCode::Created);
+        assert_eq!(resp.headers().get::<ContentType>(), Some(&ContentType::json()));
+        assert_eq!(location(&resp), Some("/elsewhere"));
+
+        let resp = ("{}", headers).respond();
+        assert_eq!(resp.status(), StatusCode::Ok);
+        assert_eq!(resp.headers().get::<ContentType>(), Some(&ContentType::json()));
+        assert_eq!(location(&resp), Some("/elsewhere"));
+    }
+
+    #[test]
+    fn inner_errors_pass_through() {
+        let none = || None::<&'static str>;
+        let failed = || Err::<&'static str, Error>(Error::OtherServersFault("boom".to_string()));
+        let is_not_found = |r: Result<Response, Error>| match r {
+            Err(Error::NotFound) => true,
+            _ => false,
+        };
+        let is_fault = |r: Result<Response, Error>| match r {
+            Err(Error::OtherServersFault(_)) => true,
+            _ => false,
+        };
+        let h = Headers::new();
+
+        assert!(is_not_found(Created::new("/users/1").with_body(none()).into_response()));
+        assert!(is_fault(Created::new("/users/1").with_body(failed()).negotiate(&h)));
+        assert!(is_not_found(Accepted(none()).into_response()));
+        assert!(is_fault(Accepted(failed()).negotiate(&h)));
+        assert!(is_not_found(Custom(StatusCode::ImATeapot, none()).into_response()));
+        assert!(is_fault(Custom(StatusCode::ImATeapot, failed()).negotiate(&h)));
+
+        assert!(is_not_found((StatusCode::Created, none()).into_response()));
+        assert!(is_fault((StatusCode::Created, failed()).negotiate(&h)));
+        assert!(is_not_found((StatusCode::Created, h.clone(), none()).into_response()));
+        assert!(is_fault((StatusCode::Created, h.clone(), failed()).negotiate(&h)));
+        assert!(is_not_found((none(), h.clone()).into_response()));
+        assert!(is_fault((failed(), h.clone()).negotiate(&h)));
+
+        let resp = Created::new("/users/1").with_body(Some("made")).negotiate(&h).unwrap();
+        assert_eq!(resp.status(), StatusCode::Created);
+        assert_eq!(location(&resp), Some("/users/1"));
+    }
+}
@@ -0,0 +1,205 @@
+use std::io;
+
+use bytes::Bytes;
+use futures::{Future, Sink, Stream, Poll, Async};
+use hyper::{self, Body, Chunk};
+use hyper::header::ContentType;
+use hyper::mime::{self, Mime};
+use serde::Serialize;
+use serde_json;
+use tokio_core::reactor::Handle;
+
+use {Responder, Response, Error};
+
+/// Response body produced by a stream, sent chunked as items arrive.
+///
+/// The stream is polled on the reactor only when the connection has room
+/// for another chunk, so a slow client slows the producer down.
+/// An error ends the body early, the status line is already out by then.
+/// Take `handle: Handle` as a handler argument to build one.
+pub struct Streamed<S> {
+    stream: S,
+    handle: Handle,
+    content_type: Mime,
+}
+
+/// Serializes a stream of items as one JSON array, see `Streamed::json_array`.
+pub struct JsonArray<S> {
+    items: S,
+    count: usize,
+    closed: bool,
+}
+
+/// Serializes a stream of items one JSON value per line, see `Streamed::ndjson`.
+pub struct JsonLines<S> {
+    items: S,
+}
+
+impl<S> Streamed<S>
+where
+    S: Stream<Item = Bytes> + 'static,
+    S::Error: Into<Error>,
+{
+    /// Sends `stream` as `application/octet-stream`.
+    pub fn new(stream: S, handle: &Handle) -> Streamed<S> {
+        Streamed {
+            stream,
+            handle: handle.clone(),
+            content_type: mime::APPLICATION_OCTET_STREAM,
+        }
+    }
+
+    pub fn content_type(mut self, content_type: Mime) -> Streamed<S> {
+        self.content_type = content_type;
+        self
+    }
+}
+
+impl<S> Streamed<JsonArray<S>>
+where
+    S: Stream + 'static,
+    S::Item: Serialize,
+    S::Error: Into<Error>,
+{
+    /// Sends `items` as a JSON array without collecting them first.
+    pub fn json_array(items: S, handle: &Handle) -> Streamed<JsonArray<S>> {
+        let items = JsonArray {
+            items,
+            count: 0,
+            closed: false,
+        };
+        Streamed::new(items, handle).content_type(mime::APPLICATION_JSON)
+    }
+}
+
+impl<S> Streamed<JsonLines<S>>
+where
+    S: Stream + 'static,
+    S::Item: Serialize,
+    S::Error: Into<Error>,
+{
+    /// Sends `items` as newline-delimited JSON, `application/x-ndjson`.
+    pub fn ndjson(items: S, handle: &Handle) -> Streamed<JsonLines<S>> {
+        let ndjson = "application/x-ndjson".parse().expect("valid ndjson mime");
+        Streamed::new(JsonLines { items }, handle).content_type(ndjson)
+    }
+}
+
+impl<S> Responder for Streamed<S>
+where
+    S: Stream<Item = Bytes> + 'static,
+    S::Error: Into<Error>,
+{
+    fn respond(self) -> Response {
+        Response::new()
+            .with_header(ContentType(self.content_type))
+            .with_body(spawn_body(self.stream, &self.handle))
+    }
+}
+
+impl<S> Stream for JsonArray<S>
+where
+    S: Stream,
+    S::Item: Serialize,
+    S::Error: Into<Error>,
+{
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        if self.closed {
+            return Ok(Async::Ready(None));
+        }
+
+        let mut buf = match self.count {
+            0 => vec![b'['],
+            _ => vec![b','],
+        };
+        match try_ready!(self.items.poll().map_err(Into::into)) {
+            Some(item) => {
+                serde_json::to_writer(&mut buf, &item)?;
+                self.count += 1;
+            }
+            None => {
+                buf = match self.count {
+                    0 => b"[]".to_vec(),
+                    _ => b"]".to_vec(),
+                };
+                self.closed = true;
+            }
+        }
+
+        Ok(Async::Ready(Some(buf.into())))
+    }
+}
+
+impl<S> Stream for JsonLines<S>
+where
+    S: Stream,
+    S::Item: Serialize,
+    S::Error: Into<Error>,
+{
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        match try_ready!(self.items.poll().map_err(Into::into)) {
+            Some(item) => {
+                let mut buf = serde_json::to_vec(&item)?;
+                buf.push(b'\n');
+                Ok(Async::Ready(Some(buf.into())))
+            }
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+/// Forwards `stream` into a response body from a task on the reactor.
+///
+/// The task ends when the stream does, or when the next chunk finds the
+/// client gone.
+pub(crate) fn spawn_body<S>(stream: S, handle: &Handle) -> Body
+where
+    S: Stream<Item = Bytes> + 'static,
+    S::Error: Into<Error>,
+{
+    let (tx, body) = Body::pair();
+    let chunks = stream.then(|res| Ok::<_, ()>(res.map(Chunk::from).map_err(abort)));
+    handle.spawn(tx.sink_map_err(|_| ()).send_all(chunks).map(|_| ()));
+
+    body
+}
+
+/// Hyper drops the connection when the body fails.
+fn abort<E: Into<Error>>(e: E) -> hyper::Error {
+    let e = e.into();
+    let message = e.detail().unwrap_or_else(|| e.to_string());
+    hyper::Error::from(io::Error::new(io::ErrorKind::Other, message))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use tokio_core::reactor::Core;
+
+    #[test]
+    fn json_adapters() {
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let mut body = |resp: Response| {
+            let bytes = core.run(resp.body().concat2()).unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let rows = stream::iter_ok::<_, Error>(vec![1, 2, 3]);
+        assert_eq!(body(Streamed::json_array(rows, &handle).respond()), "[1,2,3]");
+
+        let rows = stream::iter_ok::<Vec<u8>, Error>(vec![]);
+        assert_eq!(body(Streamed::json_array(rows, &handle).respond()), "[]");
+
+        let rows = stream::iter_ok::<_, Error>(vec!["a", "b"]);
+        assert_eq!(body(Streamed::ndjson(rows, &handle).respond()), "\"a\"\n\"b\"\n");
+    }
+}
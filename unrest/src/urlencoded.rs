@@ -0,0 +1,359 @@
+//! Serde deserializer for `application/x-www-form-urlencoded` data,
+//! shared by query strings and form bodies.
+//!
+//! Repeated keys deserialize into sequences, empty values into `None`,
+//! bracketed keys like `a[b]=1` into nested structs and maps.
+
+use std::fmt;
+use std::error::Error as StdError;
+use std::str::FromStr;
+use std::vec;
+
+use serde::de::{self, Deserializer, DeserializeOwned, DeserializeSeed, Visitor, MapAccess,
+                SeqAccess, IntoDeserializer};
+use url::form_urlencoded;
+
+#[derive(Debug)]
+pub(crate) struct DeError(String);
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for DeError {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> DeError {
+        DeError(msg.to_string())
+    }
+}
+
+pub(crate) fn from_bytes<T: DeserializeOwned>(input: &[u8]) -> Result<T, DeError> {
+    let mut entries = Vec::new();
+
+    for (k, v) in form_urlencoded::parse(input) {
+        insert(&mut entries, &split_key(&k), v.into_owned(), &k)?;
+    }
+
+    T::deserialize(Pairs {
+        prefix: String::new(),
+        entries: entries.into_iter(),
+        value: None,
+    })
+}
+
+enum Node {
+    Leaf(Vec<String>),
+    Tree(Vec<(String, Node)>),
+}
+
+/// `a[b][]` -> `["a", "b"]`, empty brackets only mark a repeated key.
+fn split_key(key: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let (head, mut rest) = match key.find('[') {
+        Some(i) if key.ends_with(']') => (&key[..i], &key[i..]),
+        _ => return vec![key.to_string()],
+    };
+    out.push(head.to_string());
+
+    while rest.starts_with('[') {
+        let end = match rest.find(']') {
+            Some(end) => end,
+            None => break,
+        };
+        if end > 1 {
+            out.push(rest[1..end].to_string());
+        }
+        rest = &rest[end + 1..];
+    }
+    out
+}
+
+fn insert(entries: &mut Vec<(String, Node)>, path: &[String], v: String, key: &str) -> Result<(), DeError> {
+    let (head, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    let i = match entries.iter().position(|&(ref k, _)| k == head) {
+        Some(i) => i,
+        None => {
+            let node = if rest.is_empty() {
+                Node::Leaf(Vec::new())
+            } else {
+                Node::Tree(Vec::new())
+            };
+            entries.push((head.clone(), node));
+            entries.len() - 1
+        }
+    };
+
+    match (&mut entries[i].1, rest.is_empty()) {
+        (&mut Node::Leaf(ref mut values), true) => {
+            values.push(v);
+            Ok(())
+        }
+        (&mut Node::Tree(ref mut children), false) => insert(children, rest, v, key),
+        _ => Err(DeError(format!("conflicting values for field `{}`", key))),
+    }
+}
+
+fn child_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}[{}]", prefix, name)
+    }
+}
+
+struct Pairs {
+    prefix: String,
+    entries: vec::IntoIter<(String, Node)>,
+    value: Option<Values>,
+}
+
+struct Values {
+    key: String,
+    node: Node,
+}
+
+struct Items {
+    key: String,
+    nodes: vec::IntoIter<Node>,
+}
+
+impl<'de> Deserializer<'de> for Pairs {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_map(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'de> MapAccess<'de> for Pairs {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, DeError> {
+        match self.entries.next() {
+            Some((name, node)) => {
+                self.value = Some(Values {
+                    key: child_key(&self.prefix, &name),
+                    node,
+                });
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let value = self.value.take().expect("next_value_seed called before next_key_seed");
+        seed.deserialize(value)
+    }
+}
+
+impl Values {
+    fn last(&self) -> &str {
+        match self.node {
+            Node::Leaf(ref values) => values.last().map(|s| s.as_str()).unwrap_or(""),
+            Node::Tree(_) => "",
+        }
+    }
+
+    fn parse<T: FromStr>(&self) -> Result<T, DeError>
+    where
+        T::Err: fmt::Display,
+    {
+        self.last().parse().map_err(|e| {
+            DeError(format!("invalid value '{}' for field `{}`: {}", self.last(), self.key, e))
+        })
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    }
+}
+
+impl<'de> Deserializer<'de> for Values {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.node {
+            Node::Leaf(mut values) => visitor.visit_string(values.pop().unwrap_or_default()),
+            Node::Tree(children) => {
+                visitor.visit_map(Pairs {
+                    prefix: self.key,
+                    entries: children.into_iter(),
+                    value: None,
+                })
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let empty = match self.node {
+            Node::Leaf(_) => self.last().is_empty(),
+            Node::Tree(ref children) => children.is_empty(),
+        };
+        if empty {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let nodes: Vec<Node> = match self.node {
+            Node::Leaf(values) => values.into_iter().map(|v| Node::Leaf(vec![v])).collect(),
+            Node::Tree(children) => children.into_iter().map(|(_, node)| node).collect(),
+        };
+
+        visitor.visit_seq(Items {
+            key: self.key,
+            nodes: nodes.into_iter(),
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        let v = self.last().to_string();
+        visitor.visit_enum(v.into_deserializer())
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit_struct tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> SeqAccess<'de> for Items {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, DeError> {
+        match self.nodes.next() {
+            Some(node) => {
+                seed.deserialize(Values {
+                    key: self.key.clone(),
+                    node,
+                }).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Search {
+        q: String,
+        tag: Vec<String>,
+        page: Option<u32>,
+        #[serde(default)]
+        exact: bool,
+    }
+
+    #[test]
+    fn repeated_optional_default() {
+        let s: Search = from_bytes(b"q=rust+web&tag=a&tag=b&page=").unwrap();
+        assert_eq!(
+            s,
+            Search {
+                q: "rust web".to_string(),
+                tag: vec!["a".to_string(), "b".to_string()],
+                page: None,
+                exact: false,
+            }
+        );
+
+        let s: Search = from_bytes(b"q=x&tag=a&page=3&exact=true").unwrap();
+        assert_eq!(s.page, Some(3));
+        assert!(s.exact);
+
+        let e = from_bytes::<Search>(b"q=x&tag=a&page=two").unwrap_err();
+        assert!(e.to_string().contains("`page`"));
+        let e = from_bytes::<Search>(b"tag=a").unwrap_err();
+        assert!(e.to_string().contains("`q`"));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Address {
+        city: String,
+        zip: u32,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Signup {
+        name: String,
+        address: Address,
+        roles: Vec<String>,
+    }
+
+    #[test]
+    fn nested_keys() {
+        let s: Signup = from_bytes(
+            b"name=ann&address[city]=Oslo&address[zip]=150&roles[]=admin&roles[]=dev",
+        ).unwrap();
+        assert_eq!(s.address, Address { city: "Oslo".to_string(), zip: 150 });
+        assert_eq!(s.roles, vec!["admin".to_string(), "dev".to_string()]);
+
+        let e = from_bytes::<Signup>(b"name=ann&address[city]=Oslo&address[zip]=x&roles=a")
+            .unwrap_err();
+        assert!(e.to_string().contains("`address[zip]`"));
+    }
+}
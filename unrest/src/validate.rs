@@ -0,0 +1,318 @@
+//! Semantic checks that run on extracted arguments before the handler.
+//!
+//! ```ignore
+//! impl Validate for Signup {
+//!     fn validate(&self) -> Result<(), ValidationErrors> {
+//!         let mut errors = ValidationErrors::new();
+//!         errors.check("name", rules::length(&self.name, 1, 64));
+//!         errors.check("email", rules::email(&self.email));
+//!         errors.nested("address", self.address.validate());
+//!         errors.into_result()
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::ops::Deref;
+
+use futures::Future;
+
+use {Request, Params, Error, Container, Json, Query, Form, Negotiated};
+use data::FromBody;
+use from_request::FromRequest;
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Single failed rule, `code` is stable, `message` is for humans.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Failed rules by field, nested fields are named like `address.zip` or `items[1].qty`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationErrors {
+    fields: Vec<(String, ValidationError)>,
+}
+
+/// Data or request argument `T` that passed `T::validate`,
+/// otherwise the request fails with 422 listing the field errors.
+#[derive(Debug)]
+pub struct Validated<T>(pub T);
+
+impl ValidationError {
+    pub fn new(code: &'static str, message: String) -> ValidationError {
+        ValidationError { code, message }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ValidationErrors {
+    pub fn new() -> ValidationErrors {
+        ValidationErrors::default()
+    }
+
+    pub fn add(&mut self, field: &str, error: ValidationError) {
+        self.fields.push((field.to_string(), error));
+    }
+
+    /// Records the error of a rule, if it failed.
+    pub fn check(&mut self, field: &str, rule: Result<(), ValidationError>) {
+        if let Err(e) = rule {
+            self.add(field, e);
+        }
+    }
+
+    /// Records the errors of a nested value under `field`.
+    pub fn nested(&mut self, field: &str, res: Result<(), ValidationErrors>) {
+        if let Err(nested) = res {
+            for (name, e) in nested.fields {
+                let name = if name.starts_with('[') {
+                    format!("{}{}", field, name)
+                } else {
+                    format!("{}.{}", field, name)
+                };
+                self.fields.push((name, e));
+            }
+        }
+    }
+
+    /// Validates every item of `items`, errors are named `field[i].inner`.
+    pub fn each<'a, T: Validate + 'a, I: IntoIterator<Item = &'a T>>(&mut self, field: &str, items: I) {
+        for (i, item) in items.into_iter().enumerate() {
+            self.nested(&format!("{}[{}]", field, i), item.validate());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn fields(&self) -> &[(String, ValidationError)] {
+        &self.fields
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &(ref field, ref e)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", field, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Validated<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Validated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+fn validated<T: Validate>(v: T) -> Result<Validated<T>, Error> {
+    match v.validate() {
+        Ok(()) => Ok(Validated(v)),
+        Err(errors) => Err(Error::ValidationFailed(errors)),
+    }
+}
+
+impl<T: FromBody + Validate + 'static> FromBody for Validated<T> {
+    fn from_body(req: Request) -> Box<Future<Item = Self, Error = Error>> {
+        Box::new(T::from_body(req).and_then(validated))
+    }
+}
+
+impl<T: FromRequest + Validate> FromRequest for Validated<T> {
+    fn from_request(req: &Request, params: &Params, state: &Container) -> Box<Future<Item = Self, Error = Error>> {
+        Box::new(T::from_request(req, params, state).and_then(validated))
+    }
+}
+
+impl<T: Validate> Validate for Json<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.0.validate()
+    }
+}
+
+impl<T: Validate> Validate for Query<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.0.validate()
+    }
+}
+
+impl<T: Validate> Validate for Form<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.0.validate()
+    }
+}
+
+impl<T: Validate> Validate for Negotiated<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.value.validate()
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match *self {
+            Some(ref v) => v.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Rules for `ValidationErrors::check`.
+pub mod rules {
+    use std::fmt::Display;
+
+    use super::ValidationError;
+
+    /// Length in characters within `min..=max`.
+    pub fn length(value: &str, min: usize, max: usize) -> Result<(), ValidationError> {
+        let len = value.chars().count();
+        if len < min || len > max {
+            return Err(ValidationError::new(
+                "length",
+                format!("must be between {} and {} characters long", min, max),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Number of items within `min..=max`.
+    pub fn count<T>(items: &[T], min: usize, max: usize) -> Result<(), ValidationError> {
+        if items.len() < min || items.len() > max {
+            return Err(ValidationError::new(
+                "count",
+                format!("must have between {} and {} items", min, max),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Value within `min..=max`.
+    pub fn range<T: PartialOrd + Display>(value: T, min: T, max: T) -> Result<(), ValidationError> {
+        if value < min || value > max {
+            return Err(ValidationError::new(
+                "range",
+                format!("must be between {} and {}", min, max),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Non-empty after trimming whitespace.
+    pub fn required(value: &str) -> Result<(), ValidationError> {
+        if value.trim().is_empty() {
+            return Err(ValidationError::new("required", "must not be empty".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Loose `local@domain.tld` shape check, not RFC 5322.
+    pub fn email(value: &str) -> Result<(), ValidationError> {
+        let invalid = || ValidationError::new("email", "must be an email address".to_string());
+
+        let mut parts = value.splitn(2, '@');
+        let (local, domain) = match (parts.next(), parts.next()) {
+            (Some(l), Some(d)) => (l, d),
+            _ => return Err(invalid()),
+        };
+
+        let domain_ok = domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.') &&
+            !domain.contains("..") && !domain.contains('@');
+        if local.is_empty() || value.chars().any(char::is_whitespace) || !domain_ok {
+            return Err(invalid());
+        }
+        Ok(())
+    }
+
+    /// Whole value matches `re`.
+    #[cfg(feature = "regex")]
+    pub fn matches(value: &str, re: &::regex::Regex) -> Result<(), ValidationError> {
+        let whole = re.find(value).map_or(false, |m| m.start() == 0 && m.end() == value.len());
+        if !whole {
+            return Err(ValidationError::new(
+                "pattern",
+                format!("must match {}", re.as_str()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Address {
+        zip: String,
+    }
+
+    struct Signup {
+        name: String,
+        email: String,
+        age: u32,
+        addresses: Vec<Address>,
+    }
+
+    impl Validate for Address {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            errors.check("zip", rules::length(&self.zip, 4, 10));
+            errors.into_result()
+        }
+    }
+
+    impl Validate for Signup {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            errors.check("name", rules::required(&self.name));
+            errors.check("email", rules::email(&self.email));
+            errors.check("age", rules::range(self.age, 18, 130));
+            errors.each("addresses", &self.addresses);
+            errors.into_result()
+        }
+    }
+
+    #[test]
+    fn field_errors() {
+        let mut s = Signup {
+            name: "ann".to_string(),
+            email: "ann@example.com".to_string(),
+            age: 30,
+            addresses: vec![Address { zip: "0150".to_string() }],
+        };
+        assert!(s.validate().is_ok());
+
+        s.name = " ".to_string();
+        s.email = "ann@example".to_string();
+        s.addresses.push(Address { zip: "1".to_string() });
+        let errors = s.validate().unwrap_err();
+        let fields: Vec<&str> = errors.fields().iter().map(|&(ref f, _)| f.as_str()).collect();
+        assert_eq!(fields, vec!["name", "email", "addresses[1].zip"]);
+    }
+}
@@ -24,7 +24,10 @@ struct HandlerAttributes {
     method: Method,
     path: String,
     named_path_segments: Vec<String>,
+    optional_path_segments: Vec<String>,
     data: Option<String>,
+    format: Option<String>,
+    defaults: HashMap<String, String>,
 }
 
 struct HandlerImpl {
@@ -32,6 +35,7 @@ struct HandlerImpl {
     vis: Visibility,
     ha: HandlerAttributes,
     fn_args_str: HashMap<String, String>,
+    output: FunctionRetTy,
     block: Block,
 }
 
@@ -53,6 +57,7 @@ pub fn handler(attribute: TokenStream, function: TokenStream) -> TokenStream {
         name: item_fn.ident,
         vis: item_fn.vis,
         block: *item_fn.block,
+        output: item_fn.decl.output.clone(),
         ha,
         fn_args_str,
     });
@@ -60,6 +65,311 @@ pub fn handler(attribute: TokenStream, function: TokenStream) -> TokenStream {
     gen.into()
 }
 
+/// `#[catcher(404)]` on `fn(Caught) -> impl Future<Item = impl Responder, Error = Error>`,
+/// register the result with `Router::register_catcher`.
+#[proc_macro_attribute]
+pub fn catcher(attribute: TokenStream, function: TokenStream) -> TokenStream {
+    let status: u16 = attribute
+        .to_string()
+        .trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace())
+        .parse()
+        .expect("expected a status code, e.g. #[catcher(404)]");
+
+    let Item { node, .. } = syn::parse(function).unwrap();
+    let item_fn = match node {
+        ItemKind::Fn(item) => item,
+        _ => panic!("catcher attr can only be used on functions"),
+    };
+
+    let fn_args_str = extract_fn_args(&*item_fn.decl);
+    if fn_args_str.len() > 1 {
+        panic!("catcher takes at most one argument, the Caught error");
+    }
+    let arg: proc_macro2::TokenStream = match fn_args_str.keys().next() {
+        Some(name) => format!("{}: Caught", name),
+        None => "_caught: Caught".to_string(),
+    }.parse()
+        .unwrap();
+
+    let factory_fn_name = item_fn.ident;
+    let struct_name: Ident = format!("CatcherStruct_{}", factory_fn_name.as_ref()).into();
+    let mod_name: Ident = format!("___mod_catcher_{}", factory_fn_name.as_ref()).into();
+    let block = *item_fn.block;
+    let vis = item_fn.vis;
+
+    let gen = quote! {
+        #[allow(unused_imports)]
+        mod #mod_name {
+            use unrest::{Catcher, Caught, Response, Responder, Error};
+            use futures::{Future, IntoFuture};
+            use super::*;
+
+            #[allow(non_camel_case_types)]
+            struct #struct_name {}
+
+            pub(super) fn #factory_fn_name() -> Box<Catcher + 'static> {
+                Box::new(#struct_name {})
+            }
+
+            fn __catcher(#arg) -> impl Future<Item=impl Responder, Error=Error> {
+                #block
+            }
+
+            impl Catcher for #struct_name {
+                fn catch(&self, caught: Caught) -> Box<Future<Item = Response, Error = Error>> {
+                    Box::new(__catcher(caught).and_then(|r| Ok(r.respond())))
+                }
+
+                fn status(&self) -> Option<u16> {
+                    Some(#status)
+                }
+            }
+        }
+        #vis fn #factory_fn_name() -> Box<::unrest::Catcher + 'static> {
+            self::#mod_name::#factory_fn_name()
+        }
+    };
+
+    gen.into()
+}
+
+/// Implements `unrest::ResponseError`, `Display`, `Responder` and the conversion
+/// into `unrest::Error` for an enum. Every variant takes `#[status(404)]`,
+/// `#[code("user_not_found")]` and `#[message("user {0} not found")]`,
+/// the message can refer to tuple fields by index and to named fields by name.
+#[proc_macro_derive(ResponseError, attributes(status, code, message))]
+pub fn derive_response_error(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).unwrap();
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let variants = match input.body {
+        Body::Enum(BodyEnum { ref variants, .. }) => variants,
+        _ => panic!("ResponseError can only be derived for enums"),
+    };
+
+    let mut display_arms = Vec::new();
+    let mut status_arms = Vec::new();
+    let mut code_arms = Vec::new();
+
+    for variant in variants.iter() {
+        let variant = variant.into_item();
+        let ident = variant.ident;
+
+        let status = attr_literal(&variant.attrs, "status").unwrap_or_else(|| "500".to_string());
+        match status.trim_end_matches("u16").parse::<u16>() {
+            Ok(s) if s >= 100 && s <= 599 => {}
+            _ => panic!("#[status({})] on {} is not an HTTP status (100-599)", status, ident),
+        }
+        let code = attr_literal(&variant.attrs, "code")
+            .unwrap_or_else(|| format!("\"{}\"", snake_case(ident.as_ref())));
+        let message = attr_literal(&variant.attrs, "message")
+            .unwrap_or_else(|| format!("\"{}\"", ident.as_ref()));
+
+        let (fields, pattern, wild) = match variant.data {
+            VariantData::Tuple(ref fields, _) => {
+                let fields: Vec<String> = (0..fields.len()).map(|i| format!("_{}", i)).collect();
+                let binds: Vec<Ident> = fields.iter().map(|f| f.as_str().into()).collect();
+                (
+                    fields,
+                    quote! { #name::#ident( #(ref #binds),* ) },
+                    quote! { #name::#ident(..) },
+                )
+            }
+            VariantData::Struct(ref fields, _) => {
+                let binds: Vec<Ident> = fields
+                    .iter()
+                    .map(|f| f.into_item().ident.clone().expect("named field"))
+                    .collect();
+                let fields = binds.iter().map(|f| f.as_ref().to_string()).collect();
+                (
+                    fields,
+                    quote! { #name::#ident { #(ref #binds),* } },
+                    quote! { #name::#ident { .. } },
+                )
+            }
+            VariantData::Unit => (Vec::new(), quote! { #name::#ident }, quote! { #name::#ident }),
+        };
+
+        let (template, used) = message_template(&message, &fields);
+        let template = tokens(&template);
+        let used_names: Vec<Ident> = used.iter().map(|f| f.as_str().into()).collect();
+        let used_values = used_names.clone();
+        let status = tokens(&status);
+        let code = tokens(&code);
+
+        display_arms.push(quote! {
+            #pattern => write!(__formatter, #template #(, #used_names = #used_values)*),
+        });
+        status_arms.push(quote! { #wild => #status, });
+        code_arms.push(quote! { #wild => #code, });
+    }
+
+    // `Error::App` boxes the error, so the conversions need it to be 'static.
+    let mut static_where = quote!(#where_clause).to_string();
+    if static_where.trim().is_empty() {
+        static_where = "where".to_string();
+    } else if !static_where.trim().ends_with(',') {
+        static_where.push(',');
+    }
+    static_where.push_str(&format!(" {} {}: 'static", name.as_ref(), quote!(#ty_generics)));
+    let static_where = tokens(&static_where);
+
+    let gen = quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn fmt(&self, __formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                match *self {
+                    #(#display_arms)*
+                }
+            }
+        }
+
+        impl #impl_generics ::unrest::ResponseError for #name #ty_generics #where_clause {
+            fn status(&self) -> u16 {
+                match *self {
+                    #(#status_arms)*
+                }
+            }
+
+            fn code(&self) -> &'static str {
+                match *self {
+                    #(#code_arms)*
+                }
+            }
+        }
+
+        impl #impl_generics From<#name #ty_generics> for ::unrest::Error #static_where {
+            fn from(e: #name #ty_generics) -> ::unrest::Error {
+                ::unrest::Error::App(Box::new(e))
+            }
+        }
+
+        impl #impl_generics ::unrest::Responder for #name #ty_generics #static_where {
+            fn respond(self) -> ::unrest::Response {
+                ::unrest::Responder::respond(::unrest::Error::from(self))
+            }
+
+            fn into_response(self) -> Result<::unrest::Response, ::unrest::Error> {
+                Err(self.into())
+            }
+        }
+    };
+
+    gen.into()
+}
+
+/// The literal of `#[name(..)]` among `attrs` as written, e.g. `"user {0} not found"`.
+fn attr_literal(attrs: &[Attribute], name: &str) -> Option<String> {
+    for attr in attrs {
+        let list = match attr.meta_item() {
+            Some(MetaItem::List(list)) => list,
+            _ => continue,
+        };
+        if list.ident.as_ref() != name {
+            continue;
+        }
+
+        return match list.nested.into_iter().next().map(|n| n.into_item()) {
+            Some(NestedMetaItem::Literal(lit)) => Some(lit.to_string()),
+            _ => panic!("expected a literal in #[{}(..)]", name),
+        };
+    }
+    None
+}
+
+fn tokens(src: &str) -> proc_macro2::TokenStream {
+    src.parse().expect("valid tokens")
+}
+
+/// Rewrites `{0}` to `{_0}` and collects the fields the template refers to.
+fn message_template(message: &str, fields: &[String]) -> (String, Vec<String>) {
+    let mut used = Vec::new();
+    let mut out = String::new();
+    let mut rest = message;
+
+    while let Some(i) = rest.find('{') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") {
+            out.push_str("{{");
+            rest = &rest[2..];
+            continue;
+        }
+
+        let end = rest.find('}').expect("unterminated placeholder in message");
+        let inner = &rest[1..end];
+        let (name, spec) = match inner.find(':') {
+            Some(j) => (&inner[..j], &inner[j..]),
+            None => (inner, ""),
+        };
+        let field = if !name.is_empty() && name.chars().all(|c| c.is_digit(10)) {
+            format!("_{}", name)
+        } else {
+            name.to_string()
+        };
+        if !fields.contains(&field) {
+            panic!("message refers to unknown field '{}'", name);
+        }
+        if !used.contains(&field) {
+            used.push(field.clone());
+        }
+        out.push_str(&format!("{{{}{}}}", field, spec));
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+
+    (out, used)
+}
+
+fn snake_case(ident: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Builds a struct from multipart fields, every field type must implement
+/// `unrest::MultipartField`.
+#[proc_macro_derive(FromMultipart)]
+pub fn derive_from_multipart(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).unwrap();
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match input.body {
+        Body::Struct(BodyStruct { data: VariantData::Struct(ref fields, _), .. }) => fields,
+        _ => panic!("FromMultipart can only be derived for structs with named fields"),
+    };
+    let field_idents: Vec<Ident> = fields
+        .iter()
+        .map(|f| f.into_item().ident.clone().expect("named field"))
+        .collect();
+    let field_names: Vec<String> = field_idents.iter().map(|f| f.as_ref().to_string()).collect();
+    let field_idents = field_idents.iter();
+    let field_names = field_names.iter();
+
+    let gen = quote! {
+        impl #impl_generics ::unrest::FromMultipart for #name #ty_generics #where_clause {
+            fn from_multipart(mut fields: ::unrest::MultipartFields) -> Result<Self, ::unrest::Error> {
+                Ok(#name {
+                    #( #field_idents: ::unrest::MultipartField::from_field(#field_names, &mut fields)?, )*
+                })
+            }
+        }
+    };
+
+    gen.into()
+}
+
 fn parse_attribute(ts: TokenStream) -> HandlerAttributes {
     let args_str = ts.to_string();
     let args_trimmed = args_str.trim_matches(|c| c == ')' || c == '(').trim();
@@ -95,13 +405,30 @@ fn parse_attribute(ts: TokenStream) -> HandlerAttributes {
         .to_string();
 
     let named_path_segments = extract_named_path_segments(&path);
+    let optional_path_segments = extract_optional_path_segments(&path);
 
     let mut data = None;
+    let mut format = None;
+    let mut defaults = HashMap::new();
 
     for nested_meta_item in meta_attr_iter {
         let item = nested_meta_item.into_item();
         let nv = match item {
             NestedMetaItem::MetaItem(MetaItem::NameValue(nv)) => nv,
+            NestedMetaItem::MetaItem(MetaItem::List(list)) => {
+                if list.ident.as_ref() != "default" {
+                    panic!("unknown list '{}' in args", list.ident.as_ref());
+                }
+                for nested in list.nested.into_iter() {
+                    match nested.into_item() {
+                        NestedMetaItem::MetaItem(MetaItem::NameValue(nv)) => {
+                            defaults.insert(nv.ident.as_ref().to_string(), nv.lit.to_string());
+                        }
+                        _ => panic!("expect key-value pairs in default(..)"),
+                    }
+                }
+                continue;
+            }
             _ => panic!("expect key-value attr after path"),
         };
 
@@ -115,6 +442,18 @@ fn parse_attribute(ts: TokenStream) -> HandlerAttributes {
                         .to_string(),
                 )
             }
+            "query" => {
+                panic!("query = \"..\" was removed, take a Query<T> argument instead")
+            }
+            "format" => {
+                format = Some(
+                    nv.lit
+                        .to_string()
+                        .trim_matches(|c| c == '"')
+                        .trim()
+                        .to_string(),
+                )
+            }
             i => panic!("unknown key '{}' in args", i),
         }
     }
@@ -124,13 +463,17 @@ fn parse_attribute(ts: TokenStream) -> HandlerAttributes {
         method,
         path,
         named_path_segments,
+        optional_path_segments,
         data,
+        format,
+        defaults,
     }
 }
 
 fn extract_named_path_segments(i: &str) -> Vec<String> {
     let mut out = Vec::new();
     for s in i.split('/') {
+        let s = s.trim_matches(|c| c == '(' || c == ')');
         if s.len() > 0 && s.as_bytes()[0] == b':' {
             out.push(s[1..].to_string())
         }
@@ -138,6 +481,21 @@ fn extract_named_path_segments(i: &str) -> Vec<String> {
     out
 }
 
+fn extract_optional_path_segments(i: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    for s in i.split('/') {
+        let opens = s.matches('(').count();
+        let closes = s.matches(')').count();
+        let name = s.trim_matches(|c| c == '(' || c == ')');
+        if (depth > 0 || s.starts_with('(')) && name.len() > 0 && name.as_bytes()[0] == b':' {
+            out.push(name[1..].to_string())
+        }
+        depth = depth + opens - closes;
+    }
+    out
+}
+
 fn extract_fn_args(decl: &FnDecl) -> HashMap<String, String> {
     let mut out = HashMap::new();
 
@@ -153,20 +511,6 @@ fn extract_fn_args(decl: &FnDecl) -> HashMap<String, String> {
 }
 
 fn validate_args(ha: &HandlerAttributes, fn_args: &HashMap<String, String>) {
-    let state_arg_count = fn_args.iter().fold(0, |acc, (_, v)| if v.starts_with(
-        "State <",
-    ) && v.ends_with(">")
-    {
-        acc + 1
-    } else {
-        acc
-    });
-
-    assert_eq!(
-        ha.named_path_segments.len() + state_arg_count + if ha.data.is_some() { 1 } else { 0 },
-        fn_args.len(),
-        "expected fn args count be equal to attr count"
-    );
     for segment in &ha.named_path_segments {
         fn_args.get(segment).expect(
             format!(
@@ -175,32 +519,52 @@ fn validate_args(ha: &HandlerAttributes, fn_args: &HashMap<String, String>) {
             ).as_str(),
         );
     }
+    for name in ha.defaults.keys() {
+        if !fn_args.contains_key(name) {
+            panic!("default for '{}' which is not an argument", name);
+        }
+        if ha.data.as_ref() == Some(name) {
+            panic!("default for '{}' which is the data argument", name);
+        }
+    }
     if let Some(ref data) = ha.data {
         fn_args.get(data).expect(&format!(
             "no data arg '{}' in fn args",
             data
         ));
     }
+    if let Some(ref format) = ha.format {
+        if ha.data.is_none() {
+            panic!("format = \"{}\" needs a data arg", format);
+        }
+        match format.as_str() {
+            "json" | "form" | "multipart" | "msgpack" | "cbor" | "yaml" | "toml" |
+            "protobuf" | "text" => {}
+            f => panic!("unknown data format '{}'", f),
+        }
+    }
 }
 
 
 fn impl_handler(hi: HandlerImpl) -> quote::Tokens {
     let struct_name: Ident = format!("HandlerStruct_{}", hi.name.as_ref()).into();
     let factory_fn_name = hi.name;
+    let name_str = hi.name.as_ref().to_string();
     let mod_name: Ident = format!("___mod_handler_{}", hi.name.as_ref()).into();
     let method_ident: Ident = hi.ha.method.as_name_str().into();
     let block = hi.block;
+    let output = hi.output;
     let path = hi.ha.path.clone();
     let vis = hi.vis;
 
-    let (args_parser_impl, args_applier, handler_args, data_fut, state_fut) =
+    let (args_parser_impl, args_applier, handler_args, data_fut) =
         impl_args_parser(hi.ha, hi.fn_args_str);
 
 
     quote! {
         #[allow(unused_imports)]
         mod #mod_name {
-            use unrest::{Handler, Request, Response, Params, Responder, Error, from_data_req, Container, State};
+            use unrest::{Handler, Request, Response, Params, Responder, Error, from_data_req, Container, State, Cookies, Session, CsrfToken, Query, Header, HeaderMap, FromRequest, parse_param, query_param, or_missing, HandlerArg, ArgFromQuery, ArgFromRequest};
             use futures::{Future, IntoFuture};
             use super::*;
 
@@ -213,7 +577,7 @@ fn impl_handler(hi: HandlerImpl) -> quote::Tokens {
                 Box::new(#struct_name {})
             }
 
-            fn __handler(#handler_args) -> impl Future<Item=impl Responder, Error=Error> {
+            fn __handler(#handler_args) #output {
                 #block
             }
         
@@ -222,17 +586,18 @@ fn impl_handler(hi: HandlerImpl) -> quote::Tokens {
             impl Handler for #struct_name {
                 #[allow(unused_variables)]
                 fn handle(&self, req: Request, p: Params, state: Container) -> Box<Future<Item = Response, Error = Error>> {
-                    let resp = __parse_args(p).into_future()
+                    let req_args = __req_args(&req, &p, &state);
+                    let headers = req.headers().clone();
+
+                    let resp = __parse_args(&req, p).into_future()
                     .and_then(|args| {
                         #data_fut
                     })
-                    .and_then(move |(args, data)| {
-                        Ok((args, data, (#state_fut)))
-                    })
-                    .and_then(|(args, data, state_args)| {
-                        __handler(#args_applier)
-                    }).and_then(|r| {
-                        Ok(r.respond())
+                    .join(req_args)
+                    .and_then(move |((args, data), req_args)| {
+                        __handler(#args_applier).map_err(Error::from)
+                    }).and_then(move |r| {
+                        r.negotiate(&headers)
                     });
                     
                     Box::new(resp)
@@ -244,6 +609,9 @@ fn impl_handler(hi: HandlerImpl) -> quote::Tokens {
                 fn method(&self) -> ::hyper::Method {
                     ::hyper::Method::#method_ident
                 }
+                fn name(&self) -> &'static str {
+                    #name_str
+                }
             }
         }
         #vis fn #factory_fn_name() -> Box<::unrest::Handler + 'static> {
@@ -257,7 +625,7 @@ fn impl_handler(hi: HandlerImpl) -> quote::Tokens {
 fn impl_args_parser(
     ha: HandlerAttributes,
     fn_args_str: HashMap<String, String>,
-) -> (quote::Tokens, quote::Tokens, quote::Tokens, quote::Tokens, quote::Tokens) {
+) -> (quote::Tokens, quote::Tokens, quote::Tokens, quote::Tokens) {
     use quote::Tokens;
 
     let mut body_tokens = Tokens::new();
@@ -265,18 +633,25 @@ fn impl_args_parser(
     let mut apply_tokens = Tokens::new();
     let mut handler_args_tokens = Tokens::new();
     let mut data_fut = Tokens::new();
-    let mut state_fut = Tokens::new();
+    let mut req_join_tokens = Tokens::new();
+    let mut req_ty_tokens = Tokens::new();
+    let mut req_pat = "()".to_string();
+    let mut req_tuple = String::new();
 
 
     for (i, name) in ha.named_path_segments.iter().enumerate() {
-        let ty: Ident = fn_args_str[name].clone().into();
-        let tok =
-            quote! {
-            params
-                        .find(#name)
-                        .ok_or_else(|| Error::ParamNotFound(#name))?
-                        .parse::<#ty>()
-                        .map_err(|e| { let e: Error = e.into(); e })?,
+        let ty_str = &fn_args_str[name];
+        let ty: proc_macro2::TokenStream = ty_str.parse().unwrap();
+        // `Option` arguments are `None` for a missing segment, see `OptionalParam`.
+        let missing = match ha.defaults.get(name) {
+            Some(default) => default_tokens(default),
+            None => quote! { or_missing::<#ty>(None, Error::ParamNotFound(#name))? },
+        };
+        let tok = quote! {
+            match params.find(#name) {
+                Some(v) if !v.is_empty() => parse_param::<#ty>(#name, v)?,
+                _ => #missing,
+            },
         };
 
         let ty_tok =
@@ -286,7 +661,36 @@ fn impl_args_parser(
 
         let apply_tok: proc_macro2::TokenStream = format!("args.{},", i).parse().unwrap();
 
-        let h_arg_tok: proc_macro2::TokenStream = format!("{}: {},", name, ty).parse().unwrap();
+        let h_arg_tok: proc_macro2::TokenStream = format!("{}: {},", name, ty_str).parse().unwrap();
+
+        body_tokens.append_tokens(tok);
+        ty_tokens.append_tokens(ty_tok);
+        apply_tokens.append_tokens(apply_tok);
+        handler_args_tokens.append_tokens(h_arg_tok);
+    }
+
+    // Defaults that aren't path segments name single query parameters.
+    let mut query_defaults: Vec<&String> = ha.defaults
+        .keys()
+        .filter(|name| !ha.named_path_segments.contains(name))
+        .collect();
+    query_defaults.sort();
+
+    for (i, name) in query_defaults.iter().enumerate() {
+        let name: &str = name;
+        let ty_str = &fn_args_str[name];
+        let ty: proc_macro2::TokenStream = ty_str.parse().unwrap();
+        let default = default_tokens(&ha.defaults[name]);
+
+        let tok = quote! {
+            query_param::<#ty>(req, #name)?.unwrap_or_else(|| #default),
+        };
+        let ty_tok = quote! {
+            #ty,
+        };
+        let apply_tok: proc_macro2::TokenStream =
+            format!("args.{},", ha.named_path_segments.len() + i).parse().unwrap();
+        let h_arg_tok: proc_macro2::TokenStream = format!("{}: {},", name, ty_str).parse().unwrap();
 
         body_tokens.append_tokens(tok);
         ty_tokens.append_tokens(ty_tok);
@@ -294,14 +698,27 @@ fn impl_args_parser(
         handler_args_tokens.append_tokens(h_arg_tok);
     }
 
+    let data_name = ha.data.clone();
     if let Some(name) = ha.data {
         let ty: proc_macro2::TokenStream = fn_args_str[&name].parse().unwrap();
 
-        let fut_tok =
-            quote! {
-            from_data_req(req).and_then(move |data| {
-                Ok((args, data))
-            })
+        let fut_tok = match ha.format {
+            Some(ref format) => {
+                quote! {
+                    ::unrest::require_format(req.headers(), #format).into_future()
+                    .and_then(move |_| from_data_req(req))
+                    .and_then(move |data| {
+                        Ok((args, data))
+                    })
+                }
+            }
+            None => {
+                quote! {
+                    from_data_req(req).and_then(move |data| {
+                        Ok((args, data))
+                    })
+                }
+            }
         };
 
         let apply_tok: proc_macro2::TokenStream = "data,".parse().unwrap();
@@ -318,40 +735,55 @@ fn impl_args_parser(
         data_fut.append_tokens(fut_tok);
     }
 
-    let mut state_idx = 0;
-    for (name_str, ty_str) in fn_args_str {
-        if ty_str.starts_with("State <") && ty_str.ends_with(">") {
-            let h_arg_tok: proc_macro2::TokenStream =
-                format!("{}: {},", name_str, ty_str).parse().unwrap();
-            let apply_tok: proc_macro2::TokenStream =
-                format!("state_args.{},", state_idx).parse().unwrap();
-            let ty: proc_macro2::TokenStream = ty_str[7..ty_str.len() - 1].parse().unwrap();
-            let get_state_tok =
-                quote! {
-                state.get::<#ty>()
-                .ok_or_else(|| 
-                    Error::StateNotFound(format!("{}: {}", #name_str, #ty_str))
-                )?,
-            };
-            handler_args_tokens.append_tokens(h_arg_tok);
-            apply_tokens.append_tokens(apply_tok);
-            state_fut.append_tokens(get_state_tok);
-            state_idx += 1;
-        }
+    let req_args: Vec<(&String, &String)> = fn_args_str
+        .iter()
+        .filter(|&(name, _)| {
+            !ha.named_path_segments.contains(name) && Some(name) != data_name.as_ref() &&
+                !ha.defaults.contains_key(name.as_str())
+        })
+        .collect();
+
+    for (req_idx, &(name_str, ty_str)) in req_args.iter().enumerate() {
+        let h_arg_tok: proc_macro2::TokenStream =
+            format!("{}: {},", name_str, ty_str).parse().unwrap();
+        let apply_tok: proc_macro2::TokenStream =
+            format!("req_args.{},", req_idx).parse().unwrap();
+        let ty: proc_macro2::TokenStream = ty_str.parse().unwrap();
+        let ty_tok: proc_macro2::TokenStream = format!("{},", ty_str).parse().unwrap();
+        // `FromParam` types and their `Option`s come from the query parameter
+        // of the same name, everything else goes through `FromRequest`.
+        let join_tok =
+            quote! {
+            let f = f.join((&&HandlerArg::<#ty>::new()).resolve(#name_str, req, params, state));
+        };
+        handler_args_tokens.append_tokens(h_arg_tok);
+        apply_tokens.append_tokens(apply_tok);
+        req_join_tokens.append_tokens(join_tok);
+        req_ty_tokens.append_tokens(ty_tok);
+        req_pat = format!("({}, a{})", req_pat, req_idx);
+        req_tuple.push_str(&format!("a{},", req_idx));
     }
-
-
+    let req_pat: proc_macro2::TokenStream = req_pat.parse().unwrap();
+    let req_tuple: proc_macro2::TokenStream = req_tuple.parse().unwrap();
 
     let parse_args =
         quote! {
         #[allow(unused_variables)]
-        fn __parse_args(params: Params) -> Result<(#ty_tokens), Error> {
+        fn __parse_args(req: &Request, params: Params) -> Result<(#ty_tokens), Error> {
             let args = (
                 #body_tokens
             );
 
             Ok(args)
         }
+
+        #[allow(unused_variables)]
+        fn __req_args(req: &Request, params: &Params, state: &Container) -> Box<Future<Item = (#req_ty_tokens), Error = Error>> {
+            let f = ::futures::future::ok::<(), Error>(());
+            #req_join_tokens
+
+            Box::new(f.map(|#req_pat| (#req_tuple)))
+        }
     };
 
     (
@@ -359,10 +791,18 @@ fn impl_args_parser(
         apply_tokens,
         handler_args_tokens,
         data_fut,
-        state_fut,
     )
 }
 
+/// `"a"` becomes `"a".into()` so string defaults fill `String` arguments.
+fn default_tokens(default: &str) -> proc_macro2::TokenStream {
+    if default.starts_with('"') {
+        format!("{}.into()", default).parse().unwrap()
+    } else {
+        default.parse().unwrap()
+    }
+}
+
 impl Method {
     fn as_name_str(&self) -> &'static str {
         match *self {
@@ -0,0 +1,69 @@
+extern crate unrest;
+#[macro_use]
+extern crate unrest_codegen;
+
+use std::fmt;
+
+use unrest::{Error, FromMultipart, MultipartField, ResponseError, SavedFile};
+
+
+/// Upload form, a doc comment saying "struct Dummy {" must not confuse the derive.
+#[derive(FromMultipart)]
+#[allow(dead_code)]
+struct Upload<F: MultipartField> {
+    /// Title of the struct upload, e.g. "a, b".
+    title: String,
+    tags: Vec<String>,
+    file: F,
+}
+
+fn assert_from_multipart<T: FromMultipart>() {}
+
+#[test]
+fn from_multipart_generic() {
+    assert_from_multipart::<Upload<SavedFile>>();
+    assert_from_multipart::<Upload<Option<SavedFile>>>();
+}
+
+/// Lookup failures, a doc comment saying "enum Dummy {" must not confuse the derive.
+#[derive(Debug, ResponseError)]
+#[allow(dead_code)]
+enum LookupError<K: fmt::Debug + fmt::Display> {
+    /// No entry, e.g. "enum ", "a -> b".
+    #[status(404)]
+    #[code("entry_not_found")]
+    #[message("no entry for {0}")]
+    Missing(K),
+    #[status(429)]
+    #[message("limit of {limit} lookups hit")]
+    Limited { limit: u32, retry: fn(u32) -> u32 },
+    #[status(409)]
+    #[message("conflicts with {f}")]
+    Conflict { f: String },
+    Unavailable,
+}
+
+#[test]
+fn response_error_generic() {
+    let e = LookupError::Missing("alice");
+    assert_eq!(e.status(), 404);
+    assert_eq!(e.code(), "entry_not_found");
+    assert_eq!(e.to_string(), "no entry for alice");
+
+    let e: LookupError<u64> = LookupError::Limited {
+        limit: 3,
+        retry: |n| n * 2,
+    };
+    assert_eq!(e.status(), 429);
+    assert_eq!(e.code(), "limited");
+    assert_eq!(e.to_string(), "limit of 3 lookups hit");
+
+    let e: LookupError<u64> = LookupError::Conflict { f: "bob".to_string() };
+    assert_eq!(e.status(), 409);
+    assert_eq!(e.to_string(), "conflicts with bob");
+
+    let e: Error = LookupError::<u64>::Unavailable.into();
+    assert_eq!(e.status_code().as_u16(), 500);
+    assert_eq!(e.code(), "unavailable");
+    assert_eq!(e.to_string(), "Unavailable");
+}
@@ -0,0 +1,48 @@
+#![feature(proc_macro)]
+#![feature(conservative_impl_trait)]
+
+extern crate unrest;
+extern crate unrest_codegen;
+extern crate futures;
+extern crate hyper;
+
+use futures::future::ok;
+use unrest_codegen::handler;
+
+
+#[handler(get("/list"), default(page = 1))]
+fn list(page: u32) -> impl Future<Item = impl Responder, Error = Error> {
+    ok(format!("page {}", page))
+}
+
+#[handler(get("/items(/:page)"), default(page = 1, sort = "name"))]
+fn items(page: u32, sort: String) -> impl Future<Item = impl Responder, Error = Error> {
+    ok(format!("page {} by {}", page, sort))
+}
+
+#[handler(get("/pages(/:page)"))]
+fn pages(page: ::std::option::Option<u32>) -> impl Future<Item = impl Responder, Error = Error> {
+    ok(format!("page {:?}", page))
+}
+
+#[handler(get("/search"))]
+fn search(q: String, page: Option<u32>) -> impl Future<Item = impl Responder, Error = Error> {
+    ok(format!("{} on page {:?}", q, page))
+}
+
+#[test]
+fn query_defaults() {
+    let list = list();
+    assert_eq!(list.path(), "/list");
+    assert_eq!(list.method(), hyper::Method::Get);
+    assert_eq!(list.name(), "list");
+
+    assert_eq!(items().path(), "/items(/:page)");
+}
+
+#[test]
+fn optional_args() {
+    assert_eq!(pages().path(), "/pages(/:page)");
+    assert_eq!(search().path(), "/search");
+    assert_eq!(search().name(), "search");
+}